@@ -1,3 +1,4 @@
+use crate::bus::Bus;
 use crate::error::Result;
 use crate::processor::Flisp;
 
@@ -84,7 +85,7 @@ pub enum AddrTypeOne {
 }
 
 impl AddrTypeOne {
-	pub fn get_index(&self, flisp: &Flisp, n: u8) -> usize {
+	pub fn get_index<B: Bus>(&self, flisp: &Flisp<B>, n: u8) -> u8 {
 		match self {
 			AddrTypeOne::Addr => n,
 			AddrTypeOne::nSP => n.wrapping_add(flisp.SP),
@@ -93,7 +94,6 @@ impl AddrTypeOne {
 			AddrTypeOne::AX => flisp.A.wrapping_add(flisp.X),
 			AddrTypeOne::AY => flisp.A.wrapping_add(flisp.Y),
 		}
-		.into()
 	}
 
 	pub fn write_with_next<T: fmt::Write>(&self, buf: &mut T, next: u8) -> Result<u8> {
@@ -120,13 +120,13 @@ pub enum AddrTypeTwo {
 }
 
 impl AddrTypeTwo {
-	pub fn get_value(&self, flisp: &Flisp, n: u8) -> u8 {
+	pub fn get_value<B: Bus>(&self, flisp: &mut Flisp<B>, n: u8) -> u8 {
 		match self {
 			AddrTypeTwo::Data => n,
-			AddrTypeTwo::Addr => flisp.mem[n as usize],
-			AddrTypeTwo::nSP => flisp.mem[n.wrapping_add(flisp.SP) as usize],
-			AddrTypeTwo::nX => flisp.mem[n.wrapping_add(flisp.X) as usize],
-			AddrTypeTwo::nY => flisp.mem[n.wrapping_add(flisp.Y) as usize],
+			AddrTypeTwo::Addr => flisp.read(n),
+			AddrTypeTwo::nSP => flisp.read(n.wrapping_add(flisp.SP)),
+			AddrTypeTwo::nX => flisp.read(n.wrapping_add(flisp.X)),
+			AddrTypeTwo::nY => flisp.read(n.wrapping_add(flisp.Y)),
 		}
 	}
 
@@ -154,7 +154,7 @@ pub enum AddrTypeThree {
 }
 
 impl AddrTypeThree {
-	pub fn get_index(&self, flisp: &Flisp, n: u8) -> usize {
+	pub fn get_index<B: Bus>(&self, flisp: &Flisp<B>, n: u8) -> u8 {
 		match self {
 			AddrTypeThree::Addr => n,
 			AddrTypeThree::nSP => n.wrapping_add(flisp.SP),
@@ -163,7 +163,6 @@ impl AddrTypeThree {
 			AddrTypeThree::AY => flisp.A.wrapping_add(flisp.Y),
 			AddrTypeThree::AX => flisp.A.wrapping_add(flisp.X),
 		}
-		.into()
 	}
 
 	pub fn write_with_next<T: fmt::Write>(&self, buf: &mut T, next: u8) -> Result<u8> {
@@ -188,11 +187,11 @@ pub enum AddrTypeFour {
 }
 
 impl AddrTypeFour {
-	pub fn get_value(&self, flisp: &Flisp, n: u8) -> u8 {
+	pub fn get_value<B: Bus>(&self, flisp: &mut Flisp<B>, n: u8) -> u8 {
 		match self {
-			AddrTypeFour::Addr => flisp.mem[n as usize],
+			AddrTypeFour::Addr => flisp.read(n),
 			AddrTypeFour::Data => n,
-			AddrTypeFour::nSP => n.wrapping_add(flisp.SP),
+			AddrTypeFour::nSP => flisp.read(n.wrapping_add(flisp.SP)),
 		}
 	}
 
@@ -223,7 +222,7 @@ pub enum AddrTypeFive {
 }
 
 impl AddrTypeFive {
-	pub fn get_target(&self, flisp: &Flisp, n: u8) -> u8 {
+	pub fn get_target<B: Bus>(&self, flisp: &Flisp<B>, n: u8) -> u8 {
 		match self {
 			AddrTypeFive::Addr => n,
 			AddrTypeFive::nX => n.wrapping_add(flisp.X),
@@ -0,0 +1,997 @@
+use std::{collections::BTreeMap, result};
+
+use crate::*;
+
+/// Labels and `EQU` constants by name
+pub type SymbolTable = BTreeMap<String, Symbol>;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Symbol {
+	pub value: u8,
+	/// Defined with `EQU`, so it isn't necessarily an address
+	pub constant: bool,
+}
+
+/// What went wrong on a line, before it is known which line that is
+#[derive(Debug, Clone, PartialEq)]
+struct Problem<'a> {
+	/// Slice of the line that caused it
+	token: &'a str,
+	message: String,
+	suggestion: Option<String>,
+}
+
+impl<'a> Problem<'a> {
+	fn new(token: &'a str, message: String) -> Self {
+		Problem {
+			token,
+			message,
+			suggestion: None,
+		}
+	}
+
+	fn suggest(self, suggestion: String) -> Self {
+		Problem {
+			suggestion: Some(suggestion),
+			..self
+		}
+	}
+
+	fn at(self, text: &str, line_no: usize) -> Box<Diagnostic> {
+		let diagnostic = Diagnostic::new(text, line_no, self.token, self.message);
+		Box::new(match self.suggestion {
+			Some(suggestion) => diagnostic.with_suggestion(suggestion),
+			None => diagnostic,
+		})
+	}
+}
+
+type Parsed<'a, T> = result::Result<T, Problem<'a>>;
+
+const DIRECTIVES: [&str; 4] = ["ORG", "FCB", "RMB", "EQU"];
+
+const MNEMONICS: [&str; 82] = [
+	"ADCA", "ADDA", "ANDA", "ANDCC", "ASLA", "ASL", "ASRA", "ASR", "BITA", "BLE", "BLS", "BLT",
+	"BMI", "BNE", "BPL", "BRA", "BSR", "BVC", "BVS", "BCC", "BCS", "BEQ", "BGE", "BGT", "BHI",
+	"BHS", "BLO", "CLRA", "CLR", "CMPA", "CMPX", "CMPY", "CMPSP", "COMA", "COM", "DECA", "DEC",
+	"EORA", "EXG", "INCA", "INC", "JMP", "JSR", "LDA", "LDX", "LDY", "LDSP", "LEAX", "LEAY",
+	"LEASP", "LSLA", "LSL", "LSRA", "LSR", "NEGA", "NEG", "NOP", "ORA", "ORCC", "PSHA", "PSHX",
+	"PSHY", "PSHCC", "PULA", "PULX", "PULY", "PULCC", "ROLA", "ROL", "RORA", "ROR", "RTS", "RTI",
+	"SBCA", "STA", "STX", "STY", "STSP", "SUBA", "TFR", "TSTA", "TST",
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Reg {
+	A,
+	X,
+	Y,
+	SP,
+	CC,
+}
+
+impl Reg {
+	fn parse(s: &str) -> Option<Self> {
+		let res = match s.to_ascii_uppercase().as_str() {
+			"A" => Reg::A,
+			"X" => Reg::X,
+			"Y" => Reg::Y,
+			"SP" => Reg::SP,
+			"CC" => Reg::CC,
+			_ => return None,
+		};
+		Some(res)
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Operand<'a> {
+	None,
+	/// `#Data`
+	Data(&'a str),
+	/// `Adr`, or the target of a branch
+	Addr(&'a str),
+	/// `n,SP`, `n,X` and `n,Y`
+	Offset(&'a str, Reg),
+	/// `A,X`, `X,Y`, `A,CC`, ...
+	Pair(Reg, Reg),
+	/// `,X+`
+	PostInc(Reg),
+	/// `,X-`
+	PostDec(Reg),
+	/// `,+X`
+	PreInc(Reg),
+	/// `,-X`
+	PreDec(Reg),
+}
+
+/// One operand of every shape, to list the addressing modes an instruction supports
+const SHAPES: [Operand<'static>; 23] = [
+	Operand::Data(""),
+	Operand::Addr(""),
+	Operand::Offset("", Reg::SP),
+	Operand::Offset("", Reg::X),
+	Operand::Offset("", Reg::Y),
+	Operand::Pair(Reg::A, Reg::X),
+	Operand::Pair(Reg::A, Reg::Y),
+	Operand::PostInc(Reg::X),
+	Operand::PostDec(Reg::X),
+	Operand::PreInc(Reg::X),
+	Operand::PreDec(Reg::X),
+	Operand::PostInc(Reg::Y),
+	Operand::PostDec(Reg::Y),
+	Operand::PreInc(Reg::Y),
+	Operand::PreDec(Reg::Y),
+	Operand::Pair(Reg::A, Reg::CC),
+	Operand::Pair(Reg::CC, Reg::A),
+	Operand::Pair(Reg::X, Reg::Y),
+	Operand::Pair(Reg::Y, Reg::X),
+	Operand::Pair(Reg::X, Reg::SP),
+	Operand::Pair(Reg::SP, Reg::X),
+	Operand::Pair(Reg::Y, Reg::SP),
+	Operand::Pair(Reg::SP, Reg::Y),
+];
+
+impl<'a> Operand<'a> {
+	fn parse(s: &'a str) -> Parsed<'a, Self> {
+		if s.is_empty() {
+			return Ok(Operand::None);
+		}
+		if let Some(data) = s.strip_prefix('#') {
+			return Ok(Operand::Data(data.trim()));
+		}
+		let (lhs, rhs) = match s.find(',') {
+			Some(idx) => (s[..idx].trim(), s[idx + 1..].trim()),
+			None => return Ok(Self::auto_index(s).unwrap_or(Operand::Addr(s))),
+		};
+		if lhs.is_empty() {
+			return Self::auto_index(rhs).ok_or_else(|| {
+				Problem::new(rhs, format!("invalid auto-index `{}`", rhs))
+					.suggest("use one of X+, X-, +X, -X, Y+, Y-, +Y or -Y".to_string())
+			});
+		}
+		let rhs = Reg::parse(rhs).ok_or_else(|| {
+			Problem::new(rhs, format!("expected a register, found `{}`", rhs))
+				.suggest("registers are A, X, Y, SP and CC".to_string())
+		})?;
+		let res = match Reg::parse(lhs) {
+			Some(lhs) => Operand::Pair(lhs, rhs),
+			None => Operand::Offset(lhs, rhs),
+		};
+		Ok(res)
+	}
+
+	/// The addressing mode as written in the instruction set, like `n,SP` or `,X+`
+	fn mode(&self) -> String {
+		match self {
+			Operand::None => "inherent".to_string(),
+			Operand::Data(_) => "#Data".to_string(),
+			Operand::Addr(_) => "Adr".to_string(),
+			Operand::Offset(_, reg) => format!("n,{:?}", reg),
+			Operand::Pair(lhs, rhs) => format!("{:?},{:?}", lhs, rhs),
+			Operand::PostInc(reg) => format!(",{:?}+", reg),
+			Operand::PostDec(reg) => format!(",{:?}-", reg),
+			Operand::PreInc(reg) => format!(",+{:?}", reg),
+			Operand::PreDec(reg) => format!(",-{:?}", reg),
+		}
+	}
+
+	fn auto_index(s: &str) -> Option<Self> {
+		let res = if let Some(reg) = s.strip_suffix('+') {
+			Operand::PostInc(Reg::parse(reg)?)
+		} else if let Some(reg) = s.strip_suffix('-') {
+			Operand::PostDec(Reg::parse(reg)?)
+		} else if let Some(reg) = s.strip_prefix('+') {
+			Operand::PreInc(Reg::parse(reg)?)
+		} else if let Some(reg) = s.strip_prefix('-') {
+			Operand::PreDec(Reg::parse(reg)?)
+		} else {
+			return None;
+		};
+		Some(res)
+	}
+
+	/// The expression that ends up in the operand byte, if any
+	fn expr(&self) -> Option<&'a str> {
+		match self {
+			Operand::Data(expr) | Operand::Addr(expr) | Operand::Offset(expr, _) => Some(expr),
+			_ => None,
+		}
+	}
+
+	fn type_one(&self) -> Option<AddrTypeOne> {
+		let res = match self {
+			Operand::Addr(_) => AddrTypeOne::Addr,
+			Operand::Offset(_, Reg::SP) => AddrTypeOne::nSP,
+			Operand::Offset(_, Reg::X) => AddrTypeOne::nX,
+			Operand::Offset(_, Reg::Y) => AddrTypeOne::nY,
+			Operand::Pair(Reg::A, Reg::X) => AddrTypeOne::AX,
+			Operand::Pair(Reg::A, Reg::Y) => AddrTypeOne::AY,
+			_ => return None,
+		};
+		Some(res)
+	}
+
+	fn type_two(&self) -> Option<AddrTypeTwo> {
+		let res = match self {
+			Operand::Data(_) => AddrTypeTwo::Data,
+			Operand::Addr(_) => AddrTypeTwo::Addr,
+			Operand::Offset(_, Reg::SP) => AddrTypeTwo::nSP,
+			Operand::Offset(_, Reg::X) => AddrTypeTwo::nX,
+			Operand::Offset(_, Reg::Y) => AddrTypeTwo::nY,
+			_ => return None,
+		};
+		Some(res)
+	}
+
+	fn type_three(&self) -> Option<AddrTypeThree> {
+		let res = match self {
+			Operand::Addr(_) => AddrTypeThree::Addr,
+			Operand::Offset(_, Reg::SP) => AddrTypeThree::nSP,
+			Operand::Offset(_, Reg::X) => AddrTypeThree::nX,
+			Operand::Offset(_, Reg::Y) => AddrTypeThree::nY,
+			Operand::Pair(Reg::A, Reg::X) => AddrTypeThree::AX,
+			Operand::Pair(Reg::A, Reg::Y) => AddrTypeThree::AY,
+			_ => return None,
+		};
+		Some(res)
+	}
+
+	fn type_four(&self) -> Option<AddrTypeFour> {
+		let res = match self {
+			Operand::Data(_) => AddrTypeFour::Data,
+			Operand::Addr(_) => AddrTypeFour::Addr,
+			Operand::Offset(_, Reg::SP) => AddrTypeFour::nSP,
+			_ => return None,
+		};
+		Some(res)
+	}
+
+	fn type_five(&self) -> Option<AddrTypeFive> {
+		let res = match self {
+			Operand::Addr(_) => AddrTypeFive::Addr,
+			Operand::Offset(_, Reg::X) => AddrTypeFive::nX,
+			Operand::Offset(_, Reg::Y) => AddrTypeFive::nY,
+			Operand::Pair(Reg::A, Reg::X) => AddrTypeFive::AX,
+			Operand::Pair(Reg::A, Reg::Y) => AddrTypeFive::AY,
+			_ => return None,
+		};
+		Some(res)
+	}
+
+	fn lda(&self) -> Option<LdaAddr> {
+		let res = match self {
+			Operand::Data(_) => LdaAddr::Data,
+			Operand::Addr(_) => LdaAddr::Addr,
+			Operand::Offset(_, Reg::SP) => LdaAddr::nSP,
+			Operand::Offset(_, Reg::X) => LdaAddr::nX,
+			Operand::Offset(_, Reg::Y) => LdaAddr::nY,
+			Operand::Pair(Reg::A, Reg::X) => LdaAddr::AX,
+			Operand::Pair(Reg::A, Reg::Y) => LdaAddr::AY,
+			Operand::PostInc(Reg::X) => LdaAddr::Xplus,
+			Operand::PostDec(Reg::X) => LdaAddr::Xminus,
+			Operand::PreInc(Reg::X) => LdaAddr::plusX,
+			Operand::PreDec(Reg::X) => LdaAddr::minusX,
+			Operand::PostInc(Reg::Y) => LdaAddr::Yplus,
+			Operand::PostDec(Reg::Y) => LdaAddr::Yminus,
+			Operand::PreInc(Reg::Y) => LdaAddr::plusY,
+			Operand::PreDec(Reg::Y) => LdaAddr::minusY,
+			_ => return None,
+		};
+		Some(res)
+	}
+
+	fn sta(&self) -> Option<StaAddr> {
+		let res = match self.lda()? {
+			LdaAddr::Data => return None,
+			LdaAddr::Addr => StaAddr::Addr,
+			LdaAddr::nSP => StaAddr::nSP,
+			LdaAddr::nX => StaAddr::nX,
+			LdaAddr::nY => StaAddr::nY,
+			LdaAddr::AX => StaAddr::AX,
+			LdaAddr::AY => StaAddr::AY,
+			LdaAddr::Xplus => StaAddr::Xplus,
+			LdaAddr::Xminus => StaAddr::Xminus,
+			LdaAddr::plusX => StaAddr::plusX,
+			LdaAddr::minusX => StaAddr::minusX,
+			LdaAddr::Yplus => StaAddr::Yplus,
+			LdaAddr::Yminus => StaAddr::Yminus,
+			LdaAddr::plusY => StaAddr::plusY,
+			LdaAddr::minusY => StaAddr::minusY,
+		};
+		Some(res)
+	}
+}
+
+/// Picks the instruction for a mnemonic and operand. `None` if the combination doesn't exist
+fn encode(mnemonic: &str, operand: &Operand) -> Option<Instruction> {
+	let inherent = |inst| match operand {
+		Operand::None => Some(inst),
+		_ => None,
+	};
+	let immediate = |inst| match operand {
+		Operand::Data(_) => Some(inst),
+		_ => None,
+	};
+	let branch = |inst| match operand {
+		Operand::Addr(_) => Some(inst),
+		_ => None,
+	};
+	let res = match mnemonic {
+		"ADCA" => Instruction::ADCA(operand.type_two()?),
+		"ADDA" => Instruction::ADDA(operand.type_two()?),
+		"ANDA" => Instruction::ANDA(operand.type_two()?),
+		"ANDCC" => immediate(Instruction::ANDCC)?,
+		"ASLA" | "LSLA" => inherent(Instruction::ASLA)?,
+		"ASL" | "LSL" => Instruction::ASL(operand.type_three()?),
+		"ASRA" => inherent(Instruction::ASRA)?,
+		"ASR" => Instruction::ASR(operand.type_three()?),
+		"BITA" => Instruction::BITA(operand.type_two()?),
+		"BLE" => branch(Instruction::BLE)?,
+		"BLS" => branch(Instruction::BLS)?,
+		"BLT" => branch(Instruction::BLT)?,
+		"BMI" => branch(Instruction::BMI)?,
+		"BNE" => branch(Instruction::BNE)?,
+		"BPL" => branch(Instruction::BPL)?,
+		"BRA" => branch(Instruction::BRA)?,
+		"BSR" => branch(Instruction::BSR)?,
+		"BVC" => branch(Instruction::BVC)?,
+		"BVS" => branch(Instruction::BVS)?,
+		"BCC" | "BHS" => branch(Instruction::BCC)?,
+		"BCS" | "BLO" => branch(Instruction::BCS)?,
+		"BEQ" => branch(Instruction::BEQ)?,
+		"BGE" => branch(Instruction::BGE)?,
+		"BGT" => branch(Instruction::BGT)?,
+		"BHI" => branch(Instruction::BHI)?,
+		"CLRA" => inherent(Instruction::CLRA)?,
+		"CLR" => Instruction::CLR(operand.type_three()?),
+		"CMPA" => Instruction::CMPA(operand.type_two()?),
+		"CMPX" => Instruction::CMPX(operand.type_four()?),
+		"CMPY" => Instruction::CMPY(operand.type_four()?),
+		"CMPSP" => Instruction::CMPSP(match operand {
+			Operand::Data(_) => CmpspAddr::Data,
+			Operand::Addr(_) => CmpspAddr::Addr,
+			_ => return None,
+		}),
+		"COMA" => inherent(Instruction::COMA)?,
+		"COM" => Instruction::COM(operand.type_three()?),
+		"DECA" => inherent(Instruction::DECA)?,
+		"DEC" => Instruction::DEC(operand.type_three()?),
+		"EORA" => Instruction::EORA(operand.type_two()?),
+		"EXG" => Instruction::EXG(match operand {
+			Operand::Pair(Reg::A, Reg::CC) | Operand::Pair(Reg::CC, Reg::A) => ExgAddr::ACC,
+			Operand::Pair(Reg::X, Reg::Y) | Operand::Pair(Reg::Y, Reg::X) => ExgAddr::XY,
+			Operand::Pair(Reg::X, Reg::SP) | Operand::Pair(Reg::SP, Reg::X) => ExgAddr::XSP,
+			Operand::Pair(Reg::Y, Reg::SP) | Operand::Pair(Reg::SP, Reg::Y) => ExgAddr::YSP,
+			_ => return None,
+		}),
+		"INCA" => inherent(Instruction::INCA)?,
+		"INC" => Instruction::INC(operand.type_three()?),
+		"JMP" => Instruction::JMP(operand.type_five()?),
+		"JSR" => Instruction::JSR(operand.type_five()?),
+		"LDA" => Instruction::LDA(operand.lda()?),
+		"LDX" => Instruction::LDX(operand.type_two()?),
+		"LDY" => Instruction::LDY(operand.type_two()?),
+		"LDSP" => Instruction::LDSP(operand.type_two()?),
+		"LEAX" => Instruction::LEAX(match operand {
+			Operand::Offset(_, Reg::X) => LeaxAddr::nX,
+			Operand::Offset(_, Reg::SP) => LeaxAddr::nSP,
+			_ => return None,
+		}),
+		"LEAY" => Instruction::LEAY(match operand {
+			Operand::Offset(_, Reg::Y) => LeayAddr::nY,
+			Operand::Offset(_, Reg::SP) => LeayAddr::nSP,
+			_ => return None,
+		}),
+		"LEASP" => Instruction::LEASP(match operand {
+			Operand::Offset(_, Reg::X) => LeaspAddr::nX,
+			Operand::Offset(_, Reg::Y) => LeaspAddr::nY,
+			Operand::Offset(_, Reg::SP) => LeaspAddr::nSP,
+			_ => return None,
+		}),
+		"LSRA" => inherent(Instruction::LSRA)?,
+		"LSR" => Instruction::LSR(operand.type_three()?),
+		"NEGA" => inherent(Instruction::NEGA)?,
+		"NEG" => Instruction::NEG(operand.type_three()?),
+		"NOP" => inherent(Instruction::NOP)?,
+		"ORA" => Instruction::ORA(operand.type_two()?),
+		"ORCC" => immediate(Instruction::ORCC)?,
+		"PSHA" => inherent(Instruction::PSHA)?,
+		"PSHX" => inherent(Instruction::PSHX)?,
+		"PSHY" => inherent(Instruction::PSHY)?,
+		"PSHCC" => inherent(Instruction::PSHCC)?,
+		"PULA" => inherent(Instruction::PULA)?,
+		"PULX" => inherent(Instruction::PULX)?,
+		"PULY" => inherent(Instruction::PULY)?,
+		"PULCC" => inherent(Instruction::PULCC)?,
+		"ROLA" => inherent(Instruction::ROLA)?,
+		"ROL" => Instruction::ROL(operand.type_three()?),
+		"RORA" => inherent(Instruction::RORA)?,
+		"ROR" => Instruction::ROR(operand.type_three()?),
+		"RTS" => inherent(Instruction::RTS)?,
+		"RTI" => inherent(Instruction::RTI)?,
+		"SBCA" => Instruction::SBCA(operand.type_two()?),
+		"STA" => Instruction::STA(operand.sta()?),
+		"STX" => Instruction::STX(operand.type_one()?),
+		"STY" => Instruction::STY(operand.type_one()?),
+		"STSP" => Instruction::STSP(operand.type_one()?),
+		"SUBA" => Instruction::SUBA(operand.type_two()?),
+		"TFR" => Instruction::TFR(match operand {
+			Operand::Pair(Reg::A, Reg::CC) => TfrAddr::ACC,
+			Operand::Pair(Reg::CC, Reg::A) => TfrAddr::CCA,
+			Operand::Pair(Reg::X, Reg::Y) => TfrAddr::XY,
+			Operand::Pair(Reg::Y, Reg::X) => TfrAddr::YX,
+			Operand::Pair(Reg::X, Reg::SP) => TfrAddr::XSP,
+			Operand::Pair(Reg::SP, Reg::X) => TfrAddr::SPX,
+			Operand::Pair(Reg::Y, Reg::SP) => TfrAddr::YSP,
+			Operand::Pair(Reg::SP, Reg::Y) => TfrAddr::SPY,
+			_ => return None,
+		}),
+		"TSTA" => inherent(Instruction::TSTA)?,
+		"TST" => Instruction::TST(operand.type_one()?),
+		_ => return None,
+	};
+	Some(res)
+}
+
+/// One line split into its fields, with the comment removed
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Line<'a> {
+	label: Option<&'a str>,
+	mnemonic: Option<&'a str>,
+	operand: &'a str,
+}
+
+impl<'a> Line<'a> {
+	fn parse(line: &'a str) -> Self {
+		let line = strip_comment(line);
+		let mut rest = line.trim_start();
+		let mut label = None;
+		let starts_in_first_column = rest.len() == line.len();
+		if let Some(first) = rest.split_whitespace().next() {
+			let upper = first.to_ascii_uppercase();
+			if let Some(name) = first.strip_suffix(':') {
+				label = Some(name);
+				rest = rest[first.len()..].trim_start();
+			} else if starts_in_first_column
+				&& !MNEMONICS.contains(&upper.as_str())
+				&& !DIRECTIVES.contains(&upper.as_str())
+			{
+				label = Some(first);
+				rest = rest[first.len()..].trim_start();
+			}
+		}
+		let mnemonic = rest.split_whitespace().next();
+		let operand = mnemonic.map(|m| rest[m.len()..].trim()).unwrap_or_default();
+		Line {
+			label,
+			mnemonic,
+			operand,
+		}
+	}
+}
+
+/// Removes everything after a `;`, or the whole line if it starts with `*`
+fn strip_comment(line: &str) -> &str {
+	if line.starts_with('*') {
+		return "";
+	}
+	let mut in_quote = false;
+	for (idx, c) in line.char_indices() {
+		match c {
+			'\'' => in_quote = !in_quote,
+			';' if !in_quote => return &line[..idx],
+			_ => {}
+		}
+	}
+	line
+}
+
+fn is_valid_label(name: &str) -> bool {
+	let mut chars = name.chars();
+	let first_ok = chars
+		.next()
+		.map(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+		.unwrap_or(false);
+	first_ok
+		&& chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+		&& Reg::parse(name).is_none()
+}
+
+pub(crate) fn parse_number(s: &str) -> Option<i32> {
+	let res = if let Some(hex) = s.strip_prefix('$') {
+		i32::from_str_radix(hex, 16).ok()?
+	} else if let Some(bin) = s.strip_prefix('%') {
+		i32::from_str_radix(bin, 2).ok()?
+	} else if let Some(chr) = s.strip_prefix('\'') {
+		let mut chars = chr.strip_suffix('\'').unwrap_or(chr).chars();
+		match (chars.next(), chars.next()) {
+			(Some(c), None) if c.is_ascii() => c as i32,
+			_ => return None,
+		}
+	} else if s.starts_with(|c: char| c.is_ascii_digit()) {
+		s.parse().ok()?
+	} else {
+		return None;
+	};
+	Some(res)
+}
+
+/// Evaluates sums and differences of numbers, symbols and `*` (the current address)
+fn eval<'a>(expr: &'a str, here: u8, symbols: &SymbolTable) -> Parsed<'a, u8> {
+	let expr = expr.trim();
+	let mut total = 0;
+	let mut sign = 1;
+	let mut rest = expr;
+	loop {
+		if let Some(negated) = rest.strip_prefix('-') {
+			sign = -sign;
+			rest = negated.trim_start();
+			continue;
+		}
+		// Character literals may contain + and -
+		let end = if let Some(chr) = rest.strip_prefix('\'') {
+			match chr.find('\'') {
+				Some(idx) => idx + 2,
+				None =>
+					return Err(Problem::new(
+						rest,
+						"unterminated character literal".to_string(),
+					)),
+			}
+		} else {
+			rest.find(&['+', '-'][..]).unwrap_or(rest.len())
+		};
+		let term = rest[..end].trim();
+		let value = if term.is_empty() {
+			return Err(Problem::new(term, "expected a value".to_string()));
+		} else if term == "*" {
+			here as i32
+		} else if let Some(num) = parse_number(term) {
+			num
+		} else if let Some(symbol) = symbols.get(term) {
+			symbol.value as i32
+		} else if is_valid_label(term) {
+			let problem = Problem::new(term, format!("undefined symbol `{}`", term));
+			return Err(match closest(term, symbols.keys().map(String::as_str)) {
+				Some(name) => problem.suggest(format!("did you mean `{}`?", name)),
+				None => problem,
+			});
+		} else {
+			return Err(Problem::new(term, format!("invalid number `{}`", term)));
+		};
+		total += sign * value;
+
+		rest = rest[end..].trim_start();
+		if let Some(next) = rest.strip_prefix('+') {
+			sign = 1;
+			rest = next.trim_start();
+		} else if let Some(next) = rest.strip_prefix('-') {
+			sign = -1;
+			rest = next.trim_start();
+		} else if rest.is_empty() {
+			break;
+		} else {
+			return Err(Problem::new(rest, "expected `+` or `-`".to_string()));
+		}
+	}
+	if (-128..=255).contains(&total) {
+		Ok(total as u8)
+	} else {
+		Err(
+			Problem::new(expr, format!("{} doesn't fit in a byte", total))
+				.suggest("values must be between -128 and 255".to_string()),
+		)
+	}
+}
+
+/// Number of single character edits needed to turn `a` into `b`
+fn distance(a: &str, b: &str) -> usize {
+	let b = b.chars().collect::<Vec<_>>();
+	let mut prev = (0..=b.len()).collect::<Vec<_>>();
+	for (i, ca) in a.chars().enumerate() {
+		let mut row = vec![i + 1];
+		for (j, cb) in b.iter().enumerate() {
+			let cost = if ca == *cb { 0 } else { 1 };
+			row.push((prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1));
+		}
+		prev = row;
+	}
+	prev[b.len()]
+}
+
+/// The candidate most similar to `word`, if any is close enough to be a likely typo
+fn closest<'b>(word: &str, candidates: impl Iterator<Item = &'b str>) -> Option<&'b str> {
+	let word = word.to_ascii_uppercase();
+	candidates
+		.map(|c| (distance(&word, &c.to_ascii_uppercase()), c))
+		.filter(|(dist, _)| *dist <= 2 && *dist < word.len())
+		.min_by_key(|(dist, _)| *dist)
+		.map(|(_, c)| c)
+}
+
+/// Joins `items` as `a, b and c`
+fn list(items: &[String]) -> String {
+	match items.split_last() {
+		Some((last, [])) => last.clone(),
+		Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+		None => String::new(),
+	}
+}
+
+/// Explains why `encode` rejected a mnemonic and operand
+fn unsupported<'a>(
+	mnemonic_token: &'a str,
+	mnemonic: &str,
+	operand_token: &'a str,
+	operand: &Operand,
+) -> Problem<'a> {
+	if !MNEMONICS.contains(&mnemonic) {
+		let problem = Problem::new(
+			mnemonic_token,
+			format!("unknown mnemonic `{}`", mnemonic_token),
+		);
+		let known = MNEMONICS.iter().chain(DIRECTIVES.iter()).copied();
+		return match closest(mnemonic, known) {
+			Some(name) => problem.suggest(format!("did you mean `{}`?", name)),
+			None => problem,
+		};
+	}
+	let modes = SHAPES
+		.iter()
+		.filter(|shape| encode(mnemonic, shape).is_some())
+		.map(Operand::mode)
+		.collect::<Vec<_>>();
+	if modes.is_empty() {
+		return Problem::new(operand_token, format!("{} takes no operand", mnemonic))
+			.suggest("remove the operand".to_string());
+	}
+	let problem = match operand {
+		Operand::None => Problem::new(mnemonic_token, format!("{} needs an operand", mnemonic)),
+		_ => Problem::new(
+			operand_token,
+			format!(
+				"{} does not support {} addressing",
+				mnemonic,
+				operand.mode()
+			),
+		),
+	};
+	problem.suggest(format!("{} supports {}", mnemonic, list(&modes)))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Statement<'a> {
+	Instruction(Instruction, Option<&'a str>),
+	Bytes(Vec<&'a str>),
+}
+
+/// Where a statement came from and where it goes
+#[derive(Debug, Clone, PartialEq)]
+struct Placed<'a> {
+	line_no: usize,
+	text: &'a str,
+	location: u16,
+	statement: Statement<'a>,
+}
+
+#[derive(Debug, Default)]
+struct Assembler<'a> {
+	symbols: SymbolTable,
+	statements: Vec<Placed<'a>>,
+	location: u16,
+}
+
+impl<'a> Assembler<'a> {
+	/// Finds the size of a line and the address of its label
+	fn first_pass(&mut self, line_no: usize, text: &'a str) -> Parsed<'a, ()> {
+		let line = Line::parse(text);
+		let mnemonic = line.mnemonic.map(|m| m.to_ascii_uppercase());
+		let here = self.location as u8;
+
+		if let Some(label) = line.label {
+			if !is_valid_label(label) {
+				let problem = Problem::new(label, format!("`{}` is not a valid label", label));
+				return Err(if Reg::parse(label).is_some() {
+					problem.suggest("register names can't be used as labels".to_string())
+				} else {
+					problem.suggest(
+						"labels start with a letter, `_` or `.`, followed by letters, digits, `_` or `.`"
+							.to_string(),
+					)
+				});
+			}
+			if self.symbols.contains_key(label) {
+				return Err(Problem::new(
+					label,
+					format!("`{}` is already defined", label),
+				));
+			}
+			let constant = mnemonic.as_deref() == Some("EQU");
+			let value = if constant {
+				eval(line.operand, here, &self.symbols)?
+			} else if self.location > 0xFF {
+				return Err(Problem::new(
+					label,
+					format!("`{}` is past the end of memory", label),
+				));
+			} else {
+				here
+			};
+			self.symbols
+				.insert(label.to_string(), Symbol { value, constant });
+		}
+
+		let (mnemonic_token, mnemonic) = match (line.mnemonic, mnemonic) {
+			(Some(token), Some(m)) => (token, m),
+			_ => return Ok(()),
+		};
+		let size = match mnemonic.as_str() {
+			"EQU" if line.label.is_some() => return Ok(()),
+			"EQU" => {
+				return Err(Problem::new(
+					mnemonic_token,
+					"EQU needs a label".to_string(),
+				));
+			}
+			"ORG" => {
+				self.location = eval(line.operand, here, &self.symbols)? as u16;
+				return Ok(());
+			}
+			"RMB" => u16::from(eval(line.operand, here, &self.symbols)?),
+			"FCB" => {
+				let values = line.operand.split(',').map(str::trim).collect::<Vec<_>>();
+				let size = values.len() as u16;
+				self.place(line_no, text, Statement::Bytes(values));
+				size
+			}
+			_ => {
+				let operand = Operand::parse(line.operand)?;
+				let inst = encode(&mnemonic, &operand).ok_or_else(|| {
+					unsupported(mnemonic_token, &mnemonic, line.operand, &operand)
+				})?;
+				self.place(line_no, text, Statement::Instruction(inst, operand.expr()));
+				u16::from(inst.size())
+			}
+		};
+		match self.location.checked_add(size) {
+			Some(end) if end <= 0x100 => {
+				self.location = end;
+				Ok(())
+			}
+			_ => Err(
+				Problem::new(mnemonic_token, "program doesn't fit in memory".to_string()).suggest(
+					format!(
+						"this line ends at ${:X}",
+						u32::from(self.location) + u32::from(size) - 1
+					),
+				),
+			),
+		}
+	}
+
+	fn place(&mut self, line_no: usize, text: &'a str, statement: Statement<'a>) {
+		self.statements.push(Placed {
+			line_no,
+			text,
+			location: self.location,
+			statement,
+		});
+	}
+
+	/// Every symbol is known, writes the bytes of one statement
+	fn emit(&self, placed: &Placed<'a>, mem: &mut [u8; 256]) -> Parsed<'a, ()> {
+		let adr = placed.location as u8;
+		match &placed.statement {
+			Statement::Bytes(values) =>
+				for (offset, value) in values.iter().enumerate() {
+					mem[adr as usize + offset] = eval(value, adr, &self.symbols)?;
+				},
+			Statement::Instruction(inst, expr) => {
+				mem[adr as usize] = (*inst).into();
+				if let Some(expr) = expr {
+					let operand = eval(expr, adr, &self.symbols)?;
+					mem[adr as usize + 1] = if inst.is_branch() {
+						operand.wrapping_sub(adr.wrapping_add(inst.size()))
+					} else {
+						operand
+					};
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Assembles FLISP source into a memory image and resets the processor, so PC is taken from
+/// the reset vector at $FF if the program sets it
+pub fn assemble(source: &str) -> result::Result<Flisp, Box<Diagnostic>> {
+	assemble_with_symbols(source).map(|(flisp, _)| flisp)
+}
+
+/// Like [`assemble`], but also returns the address of every label and the value of every
+/// `EQU`
+pub fn assemble_with_symbols(
+	source: &str,
+) -> result::Result<(Flisp, SymbolTable), Box<Diagnostic>> {
+	let mut asm = Assembler::default();
+	for (idx, text) in source.lines().enumerate() {
+		asm.first_pass(idx + 1, text)
+			.map_err(|problem| problem.at(text, idx + 1))?;
+	}
+
+	let mut mem = [0; 256];
+	for placed in &asm.statements {
+		asm.emit(placed, &mut mem)
+			.map_err(|problem| problem.at(placed.text, placed.line_no))?;
+	}
+
+	Ok((Flisp::new(mem), asm.symbols))
+}
+
+#[cfg(test)]
+mod test {
+	use std::convert::TryFrom;
+
+	use super::*;
+
+	#[test]
+	fn every_opcode() {
+		// Each instruction disassembled to text should assemble back to the same opcode
+		for opcode in 0..=255u8 {
+			let inst = match Instruction::try_from(opcode) {
+				Ok(inst) => inst,
+				Err(_) => continue,
+			};
+			let mut flisp = Flisp::default();
+			flisp.mem[0x20] = opcode;
+			flisp.mem[0x21] = 0x10;
+			let mut text = String::new();
+			let next = flisp.print_disassembly(&mut text, 0x20).unwrap();
+			assert_eq!(next - 0x20, inst.size(), "{:?} `{}`", inst, text);
+			let source = format!("\tORG $20\n\t{}\n", text);
+			let assembled = assemble(&source)
+				.unwrap_or_else(|e| panic!("{:02X} {:?} `{}`: {:?}", opcode, inst, text, e));
+			assert_eq!(u8::from(inst), opcode);
+			assert_eq!(assembled.mem[0x20], opcode, "{:?} `{}`", inst, text);
+		}
+	}
+
+	#[test]
+	fn labels_and_directives() {
+		let source = "\
+* Count down from COUNT
+COUNT   EQU     3
+        ORG     $20
+start:  LDA     #COUNT
+loop    DECA            ; Branches backwards
+        BNE     loop
+        STA     result,SP
+        BRA     *
+result  RMB     1
+table   FCB     1,$02,%11,'A',-1,end-table
+end
+        ORG     $FF
+        FCB     start
+";
+		let (flisp, symbols) = assemble_with_symbols(source).unwrap();
+		assert_eq!(
+			flisp.mem[0x20..0x2E],
+			[0xF0, 0x03, 0x08, 0x25, 0xFD, 0xE2, 0x29, 0x21, 0xFE, 0x00, 0x01, 0x02, 0x03, 0x41]
+		);
+		assert_eq!(flisp.mem[0x2E..0x30], [0xFF, 0x06]);
+		assert_eq!(flisp.PC, 0x20);
+		assert_eq!(symbols["COUNT"].value, 3);
+		assert_eq!(symbols["loop"].value, 0x22);
+		assert_eq!(symbols["result"].value, 0x29);
+		assert_eq!(symbols["table"].value, 0x2A);
+		assert_eq!(symbols["end"].value, 0x30);
+		assert!(symbols["COUNT"].constant);
+		assert!(!symbols["loop"].constant);
+	}
+
+	#[test]
+	fn addressing_modes() {
+		let source = "\
+	LDA	,X+
+	STA	,-Y
+	LDA	A,Y
+	JSR	A,X
+	LDX	#$10
+	ADDA	4,Y
+	TFR	SP,X
+	EXG	A,CC
+	LEASP	-2,SP
+	CMPX	1,SP
+	ANDCC	#%11101111
+";
+		let flisp = assemble(source).unwrap();
+		assert_eq!(
+			flisp.mem[0x00..0x12],
+			[
+				0xF5, 0xEE, 0xFA, 0x64, 0x90, 0x10, 0xD6, 0x04, 0x1D, 0x9F, 0xBE, 0xFE, 0xBC, 0x01,
+				0x01, 0xEF, 0x00, 0x00
+			]
+		);
+	}
+
+	#[test]
+	fn errors() {
+		// Source, line, column, token and the start of the message
+		let cases = [
+			("\tLDX\t,X+\n", 1, 6, ",X+", "LDX does not support ,X+"),
+			("\tLDA\t#undefined\n", 1, 7, "undefined", "undefined symbol"),
+			("\tFOO\t1\n", 1, 2, "FOO", "unknown mnemonic"),
+			("\tLDA\t#256\n", 1, 7, "256", "256 doesn't fit"),
+			(
+				"dup\tNOP\ndup\tNOP\n",
+				2,
+				1,
+				"dup",
+				"`dup` is already defined",
+			),
+			(
+				"\tORG\t$FF\n\tLDA\t#1\n",
+				2,
+				2,
+				"LDA",
+				"program doesn't fit",
+			),
+			(
+				"\tORG\t$F0\n\tRMB\t$20\n\tORG\t$20\n",
+				2,
+				2,
+				"RMB",
+				"program doesn't fit",
+			),
+			("\tINCA\t1\n", 1, 7, "1", "INCA takes no operand"),
+			("\tLDA\t1,Q\n", 1, 8, "Q", "expected a register"),
+			("\tFCB\t1, 2x\n", 1, 9, "2x", "invalid number"),
+		];
+		for (source, line, column, token, message) in cases.iter() {
+			let err = assemble(source).unwrap_err();
+			assert_eq!(
+				(err.line, err.column, err.token.as_str()),
+				(*line, *column, *token),
+				"{}",
+				err
+			);
+			assert!(err.message.starts_with(message), "{}", err);
+		}
+
+		// Reported where it first runs past the end, however much more follows
+		let source = format!("\tORG\t$FF\n{}", "\tRMB\t255\n".repeat(300));
+		let err = assemble(&source).unwrap_err();
+		assert_eq!(err.line, 2);
+		assert_eq!(err.suggestion.as_deref(), Some("this line ends at $1FD"));
+	}
+
+	#[test]
+	fn suggestions() {
+		let err = assemble("\tLDX\t,X+\n").unwrap_err();
+		assert_eq!(
+			err.suggestion.as_deref(),
+			Some("LDX supports #Data, Adr, n,SP, n,X and n,Y")
+		);
+		let err = assemble("\tLDAA\t#1\n").unwrap_err();
+		assert_eq!(err.suggestion.as_deref(), Some("did you mean `LDA`?"));
+		let err = assemble("loop\tBRA\tlop\n").unwrap_err();
+		assert_eq!(err.suggestion.as_deref(), Some("did you mean `loop`?"));
+	}
+
+	#[test]
+	fn snippet() {
+		let err = assemble("\tNOP\n\tSTA\t#1\n")
+			.unwrap_err()
+			.in_file("prog.sflisp");
+		assert_eq!(
+			err.to_string(),
+			"\
+error: STA does not support #Data addressing
+ --> prog.sflisp:2:6
+  |
+2 | \tSTA\t#1
+  | \t   \t^^
+  = help: STA supports Adr, n,SP, n,X, n,Y, A,X, A,Y, ,X+, ,X-, ,+X, ,-X, ,Y+, ,Y-, ,+Y and ,-Y"
+		);
+	}
+}
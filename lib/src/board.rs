@@ -0,0 +1,205 @@
+use crate::*;
+
+/// The DigiFlisp board: RAM with a device port at $FB and one at $FC. Ports with nothing
+/// attached behave like RAM
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+	pub ram: [u8; 256],
+	pub fb: IoDevice,
+	pub fc: IoDevice,
+}
+
+impl Board {
+	pub fn new(ram: [u8; 256]) -> Self {
+		Board {
+			ram,
+			fb: IoDevice::Nothing,
+			fc: IoDevice::Nothing,
+		}
+	}
+
+	fn port(&self, adr: u8) -> Option<&IoDevice> {
+		match adr {
+			0xFB => Some(&self.fb),
+			0xFC => Some(&self.fc),
+			_ => None,
+		}
+	}
+
+	fn port_mut(&mut self, adr: u8) -> Option<&mut IoDevice> {
+		match adr {
+			0xFB => Some(&mut self.fb),
+			0xFC => Some(&mut self.fc),
+			_ => None,
+		}
+	}
+}
+
+impl Bus for Board {
+	fn read(&mut self, adr: u8) -> u8 {
+		let value = self.peek(adr);
+		if let Some(dev) = self.port_mut(adr) {
+			dev.acknowledge();
+		}
+		value
+	}
+
+	fn write(&mut self, adr: u8, val: u8) {
+		let handled = self
+			.port_mut(adr)
+			.map(|dev| dev.write(val))
+			.unwrap_or(false);
+		if !handled {
+			self.ram[adr as usize] = val;
+		}
+	}
+
+	fn peek(&self, adr: u8) -> u8 {
+		self.port(adr)
+			.and_then(IoDevice::read)
+			.unwrap_or(self.ram[adr as usize])
+	}
+
+	fn power_on(&mut self) {
+		self.ram = [0; 256];
+		self.fb.reset();
+		self.fc.reset();
+	}
+
+	fn irq(&self) -> bool {
+		self.fb.irq() || self.fc.irq()
+	}
+
+	fn tick(&mut self, cycles: u8) {
+		self.fb.tick(cycles);
+		self.fc.tick(cycles);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::*;
+
+	#[test]
+	fn io_ports() {
+		let mut ram = [0; 256];
+		// LDA $FB, STA $FC, STA $FB
+		ram[0x20..0x26].copy_from_slice(&[0xF1, 0xFB, 0xE1, 0xFC, 0xE1, 0xFB]);
+		ram[0xFF] = 0x20;
+		let mut board = Board::new(ram);
+		board.fb = IoDevice::DILSwitch(0x5A);
+		board.fc = IoDevice::Bargraph(0);
+		let mut flisp = Flisp::new(board);
+
+		for _ in 0..3 {
+			flisp.step();
+		}
+		assert_eq!(flisp.A, 0x5A);
+		assert_eq!(flisp.mem.fc, IoDevice::Bargraph(0x5A));
+		assert_eq!(flisp.mem.fb, IoDevice::DILSwitch(0x5A));
+		assert_eq!(flisp.mem.ram[0xFB], 0);
+		assert_eq!(flisp.mem.ram[0xFC], 0);
+
+		// Detached ports fall through to memory
+		flisp.mem.fc = IoDevice::Nothing;
+		flisp.PC = 0x22;
+		flisp.step();
+		assert_eq!(flisp.mem.ram[0xFC], 0x5A);
+		assert_eq!(flisp.mem.peek(0xFC), 0x5A);
+	}
+
+	#[test]
+	fn input_devices() {
+		let mut ram = [0; 256];
+		// LDA $FB, LDA $FB, LDA $FC
+		ram[0x20..0x26].copy_from_slice(&[0xF1, 0xFB, 0xF1, 0xFB, 0xF1, 0xFC]);
+		ram[0xFF] = 0x20;
+		let mut board = Board::new(ram);
+		board.fb = IoDevice::KeyPad(0);
+		board.fc = IoDevice::DILSwitch(0);
+		let mut flisp = Flisp::new(board);
+
+		flisp.mem.fb.press_key(0xC);
+		assert_eq!(flisp.mem.peek(0xFB), KEY_READY | 0xC);
+		flisp.step();
+		assert_eq!(flisp.A, KEY_READY | 0xC);
+		// The press has been seen, the key stays
+		flisp.step();
+		assert_eq!(flisp.A, 0xC);
+
+		flisp.mem.fc.toggle_switch(0);
+		flisp.mem.fc.toggle_switch(7);
+		flisp.mem.fc.toggle_switch(0);
+		flisp.step();
+		assert_eq!(flisp.A, 0x80);
+	}
+
+	#[test]
+	fn irq_flip_flop() {
+		let flisp = assemble(
+			"\
+	ORG	$20
+	ANDCC	#$EF
+wait:	BRA	wait
+handler:	INC	$50
+	STA	$FB
+	RTI
+	ORG	$FD
+	FCB	handler,0,$20
+",
+		)
+		.unwrap();
+		let mut board = Board::new(flisp.mem);
+		board.fb = IoDevice::IRQFlipFlop(0);
+		let mut flisp = Flisp::new(board);
+		flisp.SP = 0x80;
+		flisp.step();
+		flisp.step();
+		assert!(!flisp.irq_pending());
+
+		flisp.mem.fb.trigger();
+		assert!(flisp.mem.irq());
+		flisp.step();
+		assert_eq!(flisp.PC, 0x24);
+		// Still held until the handler writes the port
+		flisp.step();
+		flisp.step();
+		assert!(!flisp.mem.irq());
+		flisp.step();
+		assert_eq!(flisp.PC, 0x22);
+		assert_eq!(flisp.mem.ram[0x50], 1);
+		assert!(!flisp.irq_pending());
+	}
+
+	#[test]
+	fn timer_interrupts() {
+		let flisp = assemble(
+			"\
+	ORG	$20
+	LDA	#$84
+	STA	$FC
+	ANDCC	#$EF
+wait:	BRA	wait
+handler:	INC	$50
+	STA	$FC
+	RTI
+	ORG	$FD
+	FCB	handler,0,$20
+",
+		)
+		.unwrap();
+		let mut board = Board::new(flisp.mem);
+		board.fc = IoDevice::from_name("timer").unwrap();
+		let mut flisp = Flisp::new(board);
+		flisp.SP = 0x80;
+		for _ in 0..3 {
+			flisp.step();
+		}
+		assert_eq!(flisp.mem.peek(0xFC), 4);
+
+		// One interrupt every 64 cycles, without drifting. Stop half way through a period
+		flisp.run_for_cycles(64 * 10 + 32);
+		assert_eq!(flisp.mem.ram[0x50], 10);
+		assert!(!flisp.mem.irq());
+	}
+}
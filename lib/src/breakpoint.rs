@@ -0,0 +1,254 @@
+use std::{collections::BTreeMap, fmt, result, str::FromStr};
+
+use crate::*;
+
+/// Register or memory cell a condition looks at
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operand {
+	A,
+	X,
+	Y,
+	SP,
+	PC,
+	CC,
+	/// `[$50]`
+	Mem(u8),
+}
+
+impl Operand {
+	pub fn value<B: Bus>(&self, flisp: &Flisp<B>) -> u8 {
+		match self {
+			Operand::A => flisp.A,
+			Operand::X => flisp.X,
+			Operand::Y => flisp.Y,
+			Operand::SP => flisp.SP,
+			Operand::PC => flisp.PC,
+			Operand::CC => flisp.CC,
+			Operand::Mem(adr) => flisp.mem.peek(*adr),
+		}
+	}
+}
+
+impl FromStr for Operand {
+	type Err = FlispError;
+
+	fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+		let res = match s.to_ascii_uppercase().as_str() {
+			"A" => Operand::A,
+			"X" => Operand::X,
+			"Y" => Operand::Y,
+			"SP" => Operand::SP,
+			"PC" => Operand::PC,
+			"CC" => Operand::CC,
+			_ => {
+				let adr = s
+					.strip_prefix('[')
+					.and_then(|s| s.strip_suffix(']'))
+					.ok_or(FlispError::InvalidCondition)?;
+				Operand::Mem(parse_byte(adr.trim())?)
+			}
+		};
+		Ok(res)
+	}
+}
+
+impl fmt::Display for Operand {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Operand::Mem(adr) => write!(f, "[${:02X}]", adr),
+			_ => write!(f, "{:?}", self),
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Comparison {
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+}
+
+impl Comparison {
+	/// Longest first, so `<=` isn't taken for `<`
+	const ALL: [(&'static str, Comparison); 6] = [
+		("==", Comparison::Eq),
+		("!=", Comparison::Ne),
+		("<=", Comparison::Le),
+		(">=", Comparison::Ge),
+		("<", Comparison::Lt),
+		(">", Comparison::Gt),
+	];
+
+	fn symbol(&self) -> &'static str {
+		Self::ALL.iter().find(|(_, cmp)| cmp == self).unwrap().0
+	}
+}
+
+/// A comparison such as `A == 0` or `[$50] >= $10`, checked when a breakpoint is reached
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Condition {
+	pub lhs: Operand,
+	pub cmp: Comparison,
+	pub rhs: u8,
+}
+
+impl Condition {
+	pub fn holds<B: Bus>(&self, flisp: &Flisp<B>) -> bool {
+		let lhs = self.lhs.value(flisp);
+		match self.cmp {
+			Comparison::Eq => lhs == self.rhs,
+			Comparison::Ne => lhs != self.rhs,
+			Comparison::Lt => lhs < self.rhs,
+			Comparison::Le => lhs <= self.rhs,
+			Comparison::Gt => lhs > self.rhs,
+			Comparison::Ge => lhs >= self.rhs,
+		}
+	}
+}
+
+impl FromStr for Condition {
+	type Err = FlispError;
+
+	fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+		let (idx, symbol, cmp) = Comparison::ALL
+			.iter()
+			.find_map(|(symbol, cmp)| s.find(symbol).map(|idx| (idx, symbol, *cmp)))
+			.ok_or(FlispError::InvalidCondition)?;
+		Ok(Condition {
+			lhs: s[..idx].trim().parse()?,
+			cmp,
+			rhs: parse_byte(s[idx + symbol.len()..].trim())?,
+		})
+	}
+}
+
+impl fmt::Display for Condition {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} {} ${:02X}", self.lhs, self.cmp.symbol(), self.rhs)
+	}
+}
+
+fn parse_byte(s: &str) -> Result<u8> {
+	assembler::parse_number(s)
+		.filter(|num| (-128..=255).contains(num))
+		.map(|num| num as u8)
+		.ok_or(FlispError::InvalidCondition)
+}
+
+/// Addresses to stop at, each with an optional condition
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Breakpoints {
+	points: BTreeMap<u8, Option<Condition>>,
+}
+
+impl Breakpoints {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a breakpoint, replacing any already at `adr`
+	pub fn set(&mut self, adr: u8, condition: Option<Condition>) {
+		self.points.insert(adr, condition);
+	}
+
+	/// Returns whether there was a breakpoint at `adr`
+	pub fn remove(&mut self, adr: u8) -> bool {
+		self.points.remove(&adr).is_some()
+	}
+
+	pub fn clear(&mut self) {
+		self.points.clear();
+	}
+
+	pub fn contains(&self, adr: u8) -> bool {
+		self.points.contains_key(&adr)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.points.is_empty()
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (u8, Option<&Condition>)> {
+		self.points.iter().map(|(adr, cond)| (*adr, cond.as_ref()))
+	}
+
+	/// Whether the processor should stop before executing the instruction at PC
+	pub fn hit<B: Bus>(&self, flisp: &Flisp<B>) -> bool {
+		match self.points.get(&flisp.PC) {
+			Some(Some(condition)) => condition.holds(flisp),
+			Some(None) => true,
+			None => false,
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn conditions() {
+		let cond: Condition = "A == 0".parse().unwrap();
+		assert_eq!(
+			cond,
+			Condition {
+				lhs: Operand::A,
+				cmp: Comparison::Eq,
+				rhs: 0
+			}
+		);
+		let cond: Condition = "[$50]>=$1f".parse().unwrap();
+		assert_eq!(cond.lhs, Operand::Mem(0x50));
+		assert_eq!(cond.cmp, Comparison::Ge);
+		assert_eq!(cond.to_string(), "[$50] >= $1F");
+		assert_eq!(
+			"sp < 300".parse::<Condition>(),
+			Err(FlispError::InvalidCondition)
+		);
+		assert_eq!(
+			"Q == 1".parse::<Condition>(),
+			Err(FlispError::InvalidCondition)
+		);
+	}
+
+	#[test]
+	fn run_until_break() {
+		let mut flisp = assemble(
+			"\
+	ORG	$20
+	LDA	#3
+loop:	DECA
+	BNE	loop
+done:	BRA	done
+	ORG	$FF
+	FCB	$20
+",
+		)
+		.unwrap();
+		let mut breakpoints = Breakpoints::new();
+		breakpoints.set(0x22, "A == 1".parse().ok());
+		breakpoints.set(0x25, None);
+
+		assert_eq!(flisp.run_until_break(&breakpoints, 1000), Some(0x22));
+		assert_eq!(flisp.A, 1);
+		assert_eq!(flisp.run_until_break(&breakpoints, 1000), Some(0x25));
+		assert_eq!(flisp.A, 0);
+
+		assert!(breakpoints.remove(0x25));
+		assert_eq!(flisp.run_until_break(&breakpoints, 100), None);
+
+		// Continuing moves off the breakpoint even without a cycle budget, and comes back to
+		// it within a small one
+		breakpoints.set(0x25, None);
+		assert_eq!(flisp.PC, 0x25);
+		assert_eq!(flisp.run_until_break(&breakpoints, 0), Some(0x25));
+		assert_eq!(flisp.run_until_break(&breakpoints, 1), Some(0x25));
+		breakpoints.set(0x20, None);
+		flisp.PC = 0x20;
+		assert_eq!(flisp.run_until_break(&breakpoints, 0), None);
+		assert_eq!(flisp.PC, 0x22);
+	}
+}
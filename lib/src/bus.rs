@@ -0,0 +1,131 @@
+/// Everything the processor can address. Implement this to map devices or ROM into the
+/// address space
+pub trait Bus {
+	/// A read made by the processor, which may have side effects on mapped devices
+	fn read(&mut self, adr: u8) -> u8;
+
+	fn write(&mut self, adr: u8, val: u8);
+
+	/// Reads without side effects, for disassembly and debugging
+	fn peek(&self, adr: u8) -> u8;
+
+	/// Returns memory and devices to their power on state
+	fn power_on(&mut self);
+
+	/// Whether a device is holding the interrupt line. It is level triggered, so the interrupt
+	/// is taken again after RTI unless the handler has made the device let go
+	fn irq(&self) -> bool {
+		false
+	}
+
+	/// Called after each step with the clock cycles it took, for devices that count time
+	fn tick(&mut self, _cycles: u8) {}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AccessKind {
+	/// Opcode or operand byte of an instruction
+	Fetch,
+	Read,
+	/// `old` is the value that was there before
+	Write {
+		old: u8,
+	},
+}
+
+/// One read or write the processor made over the bus
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Access {
+	pub adr: u8,
+	pub kind: AccessKind,
+	/// The value read or written
+	pub value: u8,
+}
+
+/// Plain RAM covering the whole address space
+impl Bus for [u8; 256] {
+	fn read(&mut self, adr: u8) -> u8 {
+		self[adr as usize]
+	}
+
+	fn write(&mut self, adr: u8, val: u8) {
+		self[adr as usize] = val;
+	}
+
+	fn peek(&self, adr: u8) -> u8 {
+		self[adr as usize]
+	}
+
+	fn power_on(&mut self) {
+		*self = [0; 256];
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::*;
+
+	/// RAM with a ROM region at $80 and up, and a counter at $FC that increments on every read
+	#[derive(Debug, Clone, PartialEq)]
+	struct Mapped {
+		ram: [u8; 256],
+		counter: u8,
+	}
+
+	impl Bus for Mapped {
+		fn read(&mut self, adr: u8) -> u8 {
+			if adr == 0xFC {
+				self.counter = self.counter.wrapping_add(1);
+				self.counter
+			} else {
+				self.ram[adr as usize]
+			}
+		}
+
+		fn write(&mut self, adr: u8, val: u8) {
+			if adr < 0x80 {
+				self.ram[adr as usize] = val;
+			}
+		}
+
+		fn peek(&self, adr: u8) -> u8 {
+			if adr == 0xFC {
+				self.counter
+			} else {
+				self.ram[adr as usize]
+			}
+		}
+
+		fn power_on(&mut self) {
+			self.ram = [0; 256];
+			self.counter = 0;
+		}
+	}
+
+	#[test]
+	fn mapped_bus() {
+		let mut ram = [0; 256];
+		// LDA $FC, STA $10, STA $90, LDA $FC
+		ram[0x20..0x28].copy_from_slice(&[0xF1, 0xFC, 0xE1, 0x10, 0xE1, 0x90, 0xF1, 0xFC]);
+		ram[0x90] = 0x42;
+		ram[0xFF] = 0x20;
+		let mut flisp = Flisp::new(Mapped { ram, counter: 0 });
+
+		for _ in 0..4 {
+			flisp.step();
+		}
+		assert_eq!(flisp.A, 2);
+		assert_eq!(flisp.mem.peek(0x10), 1);
+		assert_eq!(flisp.mem.peek(0x90), 0x42);
+
+		flisp.power_on();
+		assert_eq!(
+			flisp.mem,
+			Mapped {
+				ram: [0; 256],
+				counter: 0
+			}
+		);
+		assert_eq!(flisp.PC, 0);
+	}
+}
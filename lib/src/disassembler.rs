@@ -0,0 +1,284 @@
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt,
+};
+
+use crate::{assembler::SymbolTable, *};
+
+/// One instruction, with its branch or jump target resolved to a label
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisassembledLine {
+	pub adr: u8,
+	pub size: u8,
+	/// Label at this address, if something in the disassembly branches or jumps here
+	pub label: Option<String>,
+	/// Reached by `JSR` or `BSR`
+	pub subroutine: bool,
+	/// Absolute address a branch, `JMP` or `JSR` goes to
+	pub target: Option<u8>,
+	pub text: String,
+}
+
+impl fmt::Display for DisassembledLine {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let label = self
+			.label
+			.as_ref()
+			.map(|label| format!("{}:", label))
+			.unwrap_or_default();
+		write!(f, "{:<8}{}", label, self.text)?;
+		if self.subroutine {
+			write!(f, "  ; subroutine")?;
+		}
+		Ok(())
+	}
+}
+
+/// Name for `adr`, from the labels in `symbols` if one of them is there. `EQU` constants are
+/// passed over, their value being an address is a coincidence. Otherwise `L_4A` if a line
+/// starts at `adr`, so the label gets printed
+fn label_for(adr: u8, symbols: Option<&SymbolTable>, starts: &BTreeSet<u8>) -> Option<String> {
+	symbols
+		.and_then(|symbols| {
+			symbols
+				.iter()
+				.find(|(_, symbol)| symbol.value == adr && !symbol.constant)
+		})
+		.map(|(name, _)| name.clone())
+		.or_else(|| starts.contains(&adr).then(|| format!("L_{:02X}", adr)))
+}
+
+/// Disassembles the instruction at `adr`, without labels
+fn instruction_line<B: Bus>(flisp: &Flisp<B>, adr: u8) -> Result<DisassembledLine> {
+	let mut text = String::new();
+	let next = flisp.print_disassembly(&mut text, adr)?;
+	Ok(DisassembledLine {
+		adr,
+		size: next.wrapping_sub(adr),
+		label: None,
+		subroutine: false,
+		target: decode(&flisp.mem, adr)
+			.ok()
+			.and_then(|decoded| decoded.target()),
+		text,
+	})
+}
+
+fn data_line<B: Bus>(flisp: &Flisp<B>, adr: u8) -> DisassembledLine {
+	DisassembledLine {
+		adr,
+		size: 1,
+		label: None,
+		subroutine: false,
+		target: None,
+		text: format!("FCB     ${:02X}", flisp.mem.peek(adr)),
+	}
+}
+
+/// Replaces branch and jump operands with labels and puts the labels on the lines they point to.
+/// A target without a label, in the middle of a line, is shown as its address
+fn add_labels(lines: &mut [DisassembledLine], symbols: Option<&SymbolTable>) {
+	let starts = lines.iter().map(|line| line.adr).collect::<BTreeSet<_>>();
+	let mut labels = BTreeMap::new();
+	for line in lines.iter() {
+		if let Some(target) = line.target {
+			let name = match label_for(target, symbols, &starts) {
+				Some(name) => name,
+				None => continue,
+			};
+			let mnemonic = line.text.split_whitespace().next().unwrap_or_default();
+			let called = mnemonic == "JSR" || mnemonic == "BSR";
+			let entry = labels.entry(target).or_insert((name, false));
+			entry.1 |= called;
+		}
+	}
+	for line in lines.iter_mut() {
+		if let Some(target) = line.target {
+			let mnemonic = line.text.split_whitespace().next().unwrap_or_default();
+			line.text = match labels.get(&target) {
+				Some((name, _)) => format!("{:<8}{}", mnemonic, name),
+				None => format!("{:<8}${:02X}", mnemonic, target),
+			};
+		}
+		if let Some((name, called)) = labels.get(&line.adr) {
+			line.label = Some(name.clone());
+			line.subroutine = *called;
+		}
+	}
+}
+
+/// Disassembles from `start` to the end of memory. Branch and jump targets are shown as labels,
+/// taken from `symbols` when given or generated as `L_4A`
+pub fn disassemble<B: Bus>(
+	flisp: &Flisp<B>,
+	start: u8,
+	symbols: Option<&SymbolTable>,
+) -> Result<Vec<DisassembledLine>> {
+	let mut lines = Vec::new();
+	let mut idx = start;
+	loop {
+		let line = instruction_line(flisp, idx)?;
+		let next = idx.wrapping_add(line.size);
+		lines.push(line);
+		if next < idx {
+			break;
+		}
+		idx = next;
+	}
+	add_labels(&mut lines, symbols);
+	Ok(lines)
+}
+
+/// Addresses of every instruction that can be reached from the reset vector at $FF, the
+/// interrupt and trap vector at $FD and the current PC, following branches, jumps and calls
+pub fn find_code<B: Bus>(flisp: &Flisp<B>) -> BTreeSet<u8> {
+	let mut code = BTreeSet::new();
+	let mut pending = vec![flisp.mem.peek(0xFF), flisp.mem.peek(0xFD), flisp.PC];
+	while let Some(adr) = pending.pop() {
+		if !code.insert(adr) {
+			continue;
+		}
+		let decoded = match decode(&flisp.mem, adr) {
+			Ok(decoded) => decoded,
+			// Traps through $FD, which is already followed
+			Err(_) => {
+				code.remove(&adr);
+				continue;
+			}
+		};
+		let next = adr.wrapping_add(decoded.size);
+		if let Some(target) = decoded.target() {
+			pending.push(target);
+		}
+		match decoded.inst {
+			Instruction::RTS | Instruction::RTI | Instruction::BRA | Instruction::JMP(_) => {}
+			// Running off the end of memory
+			_ if next < adr => {}
+			_ => pending.push(next),
+		}
+	}
+	code
+}
+
+/// Disassembles all of memory, decoding only the bytes [`find_code`] reaches as instructions
+/// and showing the rest as `FCB` data
+pub fn disassemble_flow<B: Bus>(
+	flisp: &Flisp<B>,
+	symbols: Option<&SymbolTable>,
+) -> Result<Vec<DisassembledLine>> {
+	let code = find_code(flisp);
+	let mut lines = Vec::new();
+	let mut adr = 0u16;
+	while adr <= 0xFF {
+		let size = decode(&flisp.mem, adr as u8).map_or(1, |decoded| decoded.size as u16);
+		let line = if code.contains(&(adr as u8)) && adr + size <= 0x100 {
+			instruction_line(flisp, adr as u8)?
+		} else {
+			data_line(flisp, adr as u8)
+		};
+		adr += line.size as u16;
+		lines.push(line);
+	}
+	add_labels(&mut lines, symbols);
+	Ok(lines)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn labels() {
+		let source = "\
+SIZE	EQU	$20
+	ORG	$20
+start:	LDA	#3
+loop:	BSR	sub
+	DECA
+	BNE	loop
+	JMP	start
+sub:	RTS
+";
+		let flisp = assemble(source).unwrap();
+		let lines = disassemble(&flisp, 0x20, None).unwrap();
+		let text = lines
+			.iter()
+			.take(6)
+			.map(ToString::to_string)
+			.collect::<Vec<_>>();
+		assert_eq!(
+			text,
+			[
+				"L_20:   LDA     #$03",
+				"L_22:   BSR     L_29",
+				"        DECA",
+				"        BNE     L_22",
+				"        JMP     L_20",
+				"L_29:   RTS  ; subroutine",
+			]
+		);
+		assert_eq!(lines[3].target, Some(0x22));
+
+		let (flisp, symbols) = assembler::assemble_with_symbols(source).unwrap();
+		let lines = disassemble(&flisp, 0x20, Some(&symbols)).unwrap();
+		assert_eq!(lines[1].to_string(), "loop:   BSR     sub");
+		assert_eq!(lines[5].label.as_deref(), Some("sub"));
+		// The constant with the same value isn't a label
+		assert_eq!(lines[0].label.as_deref(), Some("start"));
+		assert_eq!(lines[4].to_string(), "        JMP     start");
+
+		// Starting past `start` there is no line to put L_20 on
+		let lines = disassemble(&flisp, 0x22, None).unwrap();
+		assert_eq!(lines[3].to_string(), "        JMP     $20");
+		assert_eq!(lines[0].to_string(), "L_22:   BSR     L_29");
+	}
+
+	#[test]
+	fn code_and_data() {
+		let source = "\
+	ORG	$10
+start:	LDA	table
+	BEQ	skip
+	JSR	sub
+skip:	BRA	start
+table:	FCB	$00,$01,$FF
+sub:	RTS
+handler:	RTI
+	ORG	$FD
+	FCB	handler
+	ORG	$FF
+	FCB	start
+";
+		let flisp = assemble(source).unwrap();
+		let code = find_code(&flisp);
+		assert_eq!(
+			code.into_iter().collect::<Vec<_>>(),
+			[0x10, 0x12, 0x14, 0x16, 0x1B, 0x1C]
+		);
+
+		let lines = disassemble_flow(&flisp, None).unwrap();
+		let text = lines
+			.iter()
+			.filter(|line| (0x10..0x1D).contains(&line.adr))
+			.map(ToString::to_string)
+			.collect::<Vec<_>>();
+		assert_eq!(
+			text,
+			[
+				"L_10:   LDA     $18",
+				"        BEQ     L_16",
+				"        JSR     L_1B",
+				"L_16:   BRA     L_10",
+				"        FCB     $00",
+				"        FCB     $01",
+				"        FCB     $FF",
+				"L_1B:   RTS  ; subroutine",
+				"        RTI",
+			]
+		);
+		assert_eq!(
+			lines.iter().map(|line| line.size as usize).sum::<usize>(),
+			256
+		);
+	}
+}
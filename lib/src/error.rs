@@ -6,7 +6,40 @@ pub type Result<T> = result::Result<T, FlispError>;
 pub enum FlispError {
 	InvalidOpCode(u32),
 	InvalidLineConversion(u32),
+	/// A breakpoint condition that couldn't be parsed
+	InvalidCondition,
 	FormatError,
+	/// A malformed line in an S-record or Intel HEX file. `line` is 1-based
+	InvalidRecord {
+		line: usize,
+		problem: RecordProblem,
+	},
+	/// A snapshot line that couldn't be parsed, 1-based
+	InvalidSnapshot {
+		line: usize,
+	},
+	/// A snapshot written by a newer version
+	UnsupportedSnapshotVersion(u32),
+}
+
+/// What is wrong with a record in an S-record or Intel HEX file
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RecordProblem {
+	/// Doesn't start with `S` or `:`
+	MissingStartCode,
+	UnknownType,
+	/// Characters that aren't hex digits, or an odd number of them
+	NotHex,
+	/// The byte count doesn't match the length of the line
+	WrongLength,
+	Checksum {
+		stored: u8,
+		computed: u8,
+	},
+	/// Data that doesn't fit in the 256 byte address space
+	AddressOutOfRange(u32),
+	/// The file ends without an end record
+	MissingEnd,
 }
 
 impl fmt::Display for FlispError {
@@ -22,3 +55,85 @@ impl From<fmt::Error> for FlispError {
 }
 
 impl Error for FlispError {}
+
+/// A problem in assembly source, pointing at the token that caused it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+	/// Name of the source file, if known
+	pub file: Option<String>,
+	/// 1-based line number
+	pub line: usize,
+	/// 1-based column of the first character of `token`
+	pub column: usize,
+	pub token: String,
+	pub message: String,
+	pub suggestion: Option<String>,
+	/// The full text of the offending line, used for the snippet
+	pub source_line: String,
+}
+
+impl Diagnostic {
+	/// Points at `token`, which should be a slice of `source_line`. Anything else is reported
+	/// at the start of the line
+	pub fn new(source_line: &str, line: usize, token: &str, message: String) -> Self {
+		let offset = (token.as_ptr() as usize)
+			.checked_sub(source_line.as_ptr() as usize)
+			.filter(|offset| offset + token.len() <= source_line.len())
+			.unwrap_or(0);
+		Diagnostic {
+			file: None,
+			line,
+			column: source_line[..offset].chars().count() + 1,
+			token: token.to_string(),
+			message,
+			suggestion: None,
+			source_line: source_line.to_string(),
+		}
+	}
+
+	pub fn with_suggestion(self, suggestion: String) -> Self {
+		Diagnostic {
+			suggestion: Some(suggestion),
+			..self
+		}
+	}
+
+	pub fn in_file(self, file: &str) -> Self {
+		Diagnostic {
+			file: Some(file.to_string()),
+			..self
+		}
+	}
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let gutter = " ".repeat(self.line.to_string().len());
+		writeln!(f, "error: {}", self.message)?;
+		writeln!(
+			f,
+			"{}--> {}:{}:{}",
+			gutter,
+			self.file.as_deref().unwrap_or("<source>"),
+			self.line,
+			self.column
+		)?;
+		writeln!(f, "{} |", gutter)?;
+		writeln!(f, "{} | {}", self.line, self.source_line)?;
+		// Keep tabs so the caret lines up however wide they are drawn
+		let indent = self
+			.source_line
+			.chars()
+			.take(self.column - 1)
+			.map(|c| if c == '\t' { '\t' } else { ' ' })
+			.collect::<String>();
+		let carets = "^".repeat(self.token.chars().count().max(1));
+		write!(f, "{} | {}{}", gutter, indent, carets)?;
+		if let Some(suggestion) = &self.suggestion {
+			write!(f, "\n{} = help: {}", gutter, suggestion)?;
+		}
+		Ok(())
+	}
+}
+
+impl Error for Diagnostic {}
@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+
+use crate::*;
+
+/// The states before the most recent steps, so execution can be undone. The oldest state is
+/// dropped once `capacity` is reached
+#[derive(Debug, Clone)]
+pub struct History<B: Bus = [u8; 256]> {
+	states: VecDeque<Flisp<B>>,
+	capacity: usize,
+}
+
+impl<B: Bus + Clone> History<B> {
+	pub fn new(capacity: usize) -> Self {
+		History {
+			states: VecDeque::with_capacity(capacity),
+			capacity,
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.states.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.states.is_empty()
+	}
+
+	pub fn clear(&mut self) {
+		self.states.clear();
+	}
+
+	/// Saves `flisp` as the state to go back to
+	pub fn record(&mut self, flisp: &Flisp<B>) {
+		if self.capacity == 0 {
+			return;
+		}
+		while self.states.len() >= self.capacity {
+			self.states.pop_front();
+		}
+		self.states.push_back(flisp.clone());
+	}
+
+	/// Records the state and steps
+	pub fn step(&mut self, flisp: &mut Flisp<B>) -> u8 {
+		self.record(flisp);
+		flisp.step()
+	}
+
+	/// Undoes the last recorded step. Returns false if there is nothing to go back to
+	pub fn step_back(&mut self, flisp: &mut Flisp<B>) -> bool {
+		match self.states.pop_back() {
+			Some(state) => {
+				*flisp = state;
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Goes back to the most recent state where `done` holds, dropping everything after it.
+	/// `flisp` is left alone if no recorded state matches
+	fn back_until(&mut self, flisp: &mut Flisp<B>, done: impl Fn(&Flisp<B>) -> bool) -> bool {
+		match self.states.iter().rposition(done) {
+			Some(idx) => {
+				self.states.truncate(idx + 1);
+				self.step_back(flisp)
+			}
+			None => false,
+		}
+	}
+
+	/// Goes back to the last time PC was `adr`
+	pub fn run_back_to(&mut self, flisp: &mut Flisp<B>, adr: u8) -> bool {
+		self.back_until(flisp, |state| state.PC == adr)
+	}
+
+	/// Goes back to the last time a breakpoint was hit
+	pub fn reverse_continue(&mut self, flisp: &mut Flisp<B>, breakpoints: &Breakpoints) -> bool {
+		self.back_until(flisp, |state| breakpoints.hit(state))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn back_and_forth() {
+		let mut flisp = assemble(
+			"\
+	ORG	$20
+	LDA	#3
+loop:	DECA
+	BNE	loop
+	CLRA
+	ORG	$FF
+	FCB	$20
+",
+		)
+		.unwrap();
+		let start = flisp.clone();
+		let mut history = History::new(4);
+		assert!(!history.step_back(&mut flisp));
+
+		for _ in 0..3 {
+			history.step(&mut flisp);
+		}
+		let after_three = flisp.clone();
+		history.step(&mut flisp);
+		assert!(history.step_back(&mut flisp));
+		assert_eq!(flisp, after_three);
+
+		// Only the last four states are kept
+		for _ in 0..6 {
+			history.step(&mut flisp);
+		}
+		assert_eq!(history.len(), 4);
+		while history.step_back(&mut flisp) {}
+		assert_ne!(flisp, start);
+
+		let mut history = History::new(16);
+		let mut flisp = start;
+		for _ in 0..7 {
+			history.step(&mut flisp);
+		}
+		assert_eq!(flisp.PC, 0x25);
+		assert!(history.run_back_to(&mut flisp, 0x22));
+		assert_eq!((flisp.PC, flisp.A), (0x22, 1));
+		assert!(!history.run_back_to(&mut flisp, 0x80));
+		assert_eq!(flisp.PC, 0x22);
+
+		let mut breakpoints = Breakpoints::new();
+		breakpoints.set(0x22, "A == 3".parse().ok());
+		assert!(history.reverse_continue(&mut flisp, &breakpoints));
+		assert_eq!((flisp.PC, flisp.A), (0x22, 3));
+		assert_eq!(history.len(), 1);
+	}
+}
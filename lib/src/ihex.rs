@@ -0,0 +1,158 @@
+use std::fmt::{self, Write};
+
+use crate::{error::RecordProblem, srecord::hex_bytes, *};
+
+/// Bytes written per data record
+const RECORD_LENGTH: usize = 16;
+
+const DATA: u8 = 0x00;
+const END_OF_FILE: u8 = 0x01;
+
+fn checksum(bytes: &[u8]) -> u8 {
+	bytes
+		.iter()
+		.fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+		.wrapping_neg()
+}
+
+/// Writes one record, computing the byte count and checksum
+fn write_record<W: Write>(out: &mut W, adr: u16, kind: u8, data: &[u8]) -> fmt::Result {
+	let mut bytes = vec![data.len() as u8];
+	bytes.extend_from_slice(&adr.to_be_bytes());
+	bytes.push(kind);
+	bytes.extend_from_slice(data);
+	write!(out, ":")?;
+	for byte in &bytes {
+		write!(out, "{:02X}", byte)?;
+	}
+	writeln!(out, "{:02X}", checksum(&bytes))
+}
+
+impl Flisp {
+	/// Loads a memory image from Intel HEX. Only data and end of file records are accepted,
+	/// the address space is too small for the others. Memory not covered by a record is zero
+	/// and PC is taken from the reset vector
+	pub fn from_intel_hex(text: &str) -> Result<Self> {
+		let mut flisp = Flisp::default();
+		let mut ended = false;
+		for (idx, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() {
+				continue;
+			}
+			let invalid = |problem| FlispError::InvalidRecord {
+				line: idx + 1,
+				problem,
+			};
+			let digits = line
+				.strip_prefix(':')
+				.ok_or_else(|| invalid(RecordProblem::MissingStartCode))?;
+			let bytes = hex_bytes(digits).ok_or_else(|| invalid(RecordProblem::NotHex))?;
+			if bytes.len() < 5 || bytes[0] as usize != bytes.len() - 5 {
+				return Err(invalid(RecordProblem::WrongLength));
+			}
+			let (stored, bytes) = bytes.split_last().unwrap();
+			let computed = checksum(bytes);
+			if *stored != computed {
+				return Err(invalid(RecordProblem::Checksum {
+					stored: *stored,
+					computed,
+				}));
+			}
+			let adr = u32::from(u16::from_be_bytes([bytes[1], bytes[2]]));
+			let data = &bytes[4..];
+			match bytes[3] {
+				DATA => {
+					let start = adr as usize;
+					let region = flisp
+						.mem
+						.get_mut(start..start + data.len())
+						.ok_or_else(|| invalid(RecordProblem::AddressOutOfRange(adr)))?;
+					region.copy_from_slice(data);
+				}
+				END_OF_FILE => {
+					ended = true;
+					break;
+				}
+				_ => return Err(invalid(RecordProblem::UnknownType)),
+			}
+		}
+		if !ended {
+			return Err(FlispError::InvalidRecord {
+				line: text.lines().count(),
+				problem: RecordProblem::MissingEnd,
+			});
+		}
+		flisp.reset();
+		Ok(flisp)
+	}
+}
+
+impl<B: Bus> Flisp<B> {
+	/// Writes all of memory as Intel HEX data records of 16 bytes, followed by the end of file
+	/// record
+	pub fn write_intel_hex<W: Write>(&self, out: &mut W) -> fmt::Result {
+		for start in (0..256).step_by(RECORD_LENGTH) {
+			let data = (start..start + RECORD_LENGTH)
+				.map(|adr| self.mem.peek(adr as u8))
+				.collect::<Vec<_>>();
+			write_record(out, start as u16, DATA, &data)?;
+		}
+		write_record(out, 0, END_OF_FILE, &[])
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn intel_hex() {
+		let flisp =
+			Flisp::from_intel_hex(":02002000F003EB\r\n:0100FF0020E0\n:00000001FF\n").unwrap();
+		assert_eq!(flisp.mem[0x20..0x22], [0xF0, 0x03]);
+		assert_eq!(flisp.PC, 0x20);
+
+		let mut text = String::new();
+		flisp.write_intel_hex(&mut text).unwrap();
+		assert_eq!(text.lines().count(), 17);
+		assert_eq!(
+			text.lines().nth(2),
+			Some(":10002000F0030000000000000000000000000000DD")
+		);
+		assert!(text.ends_with(":00000001FF\n"));
+		assert_eq!(Flisp::from_intel_hex(&text), Ok(flisp));
+
+		let error = |line, problem| Err(FlispError::InvalidRecord { line, problem });
+		assert_eq!(
+			Flisp::from_intel_hex(":02002000F003EC\n:00000001FF\n"),
+			error(
+				1,
+				RecordProblem::Checksum {
+					stored: 0xEC,
+					computed: 0xEB
+				}
+			)
+		);
+		assert_eq!(
+			Flisp::from_intel_hex("02002000F003EB\n"),
+			error(1, RecordProblem::MissingStartCode)
+		);
+		assert_eq!(
+			Flisp::from_intel_hex(":03002000F003EA\n"),
+			error(1, RecordProblem::WrongLength)
+		);
+		assert_eq!(
+			Flisp::from_intel_hex(":020000040000FA\n"),
+			error(1, RecordProblem::UnknownType)
+		);
+		assert_eq!(
+			Flisp::from_intel_hex(":0200FF00F0030C\n"),
+			error(1, RecordProblem::AddressOutOfRange(0xFF))
+		);
+		assert_eq!(
+			Flisp::from_intel_hex(":02002000F003EB\n"),
+			error(1, RecordProblem::MissingEnd)
+		);
+	}
+}
@@ -1,4 +1,4 @@
-use std::convert::TryFrom;
+use std::{convert::TryFrom, fmt};
 
 use crate::*;
 
@@ -418,7 +418,7 @@ impl From<Instruction> for u8 {
 			Instruction::ADDA(AddrTypeTwo::Addr) => 0xA6,
 			Instruction::ADDA(AddrTypeTwo::nSP) => 0xB6,
 			Instruction::ADDA(AddrTypeTwo::nX) => 0xC6,
-			Instruction::ADDA(AddrTypeTwo::nY) => 0xE6,
+			Instruction::ADDA(AddrTypeTwo::nY) => 0xD6,
 
 			Instruction::ANDA(AddrTypeTwo::Data) => 0x99,
 			Instruction::ANDA(AddrTypeTwo::Addr) => 0xA9,
@@ -736,9 +736,12 @@ impl Instruction {
 			| Instruction::BEQ
 			| Instruction::BGE
 			| Instruction::BGT
-			| Instruction::BHI
-			| Instruction::JMP(_)
-			| Instruction::JSR(_) => 2,
+			| Instruction::BHI => 2,
+
+			Instruction::JMP(adr) | Instruction::JSR(adr) => match adr {
+				AddrTypeFive::Addr | AddrTypeFive::nX | AddrTypeFive::nY => 2,
+				AddrTypeFive::AX | AddrTypeFive::AY => 1,
+			},
 
 			Instruction::RTS | Instruction::RTI => 1,
 
@@ -839,4 +842,575 @@ impl Instruction {
 			},
 		}
 	}
+
+	/// Whether the instruction is a relative branch (including BSR)
+	pub fn is_branch(&self) -> bool {
+		matches!(
+			self,
+			Instruction::BLE
+				| Instruction::BLS
+				| Instruction::BLT
+				| Instruction::BMI
+				| Instruction::BNE
+				| Instruction::BPL
+				| Instruction::BRA
+				| Instruction::BSR
+				| Instruction::BVC
+				| Instruction::BVS
+				| Instruction::BCC
+				| Instruction::BCS
+				| Instruction::BEQ
+				| Instruction::BGE
+				| Instruction::BGT
+				| Instruction::BHI
+		)
+	}
+
+	/// Clock cycles according to the FLEX reference (operations.mjs). Branches are listed
+	/// with their taken timing, one that isn't taken is a cycle shorter
+	pub fn cycles(&self) -> u8 {
+		match self {
+			Instruction::BSR => 5,
+			Instruction::BLE
+			| Instruction::BLS
+			| Instruction::BLT
+			| Instruction::BMI
+			| Instruction::BNE
+			| Instruction::BPL
+			| Instruction::BRA
+			| Instruction::BVC
+			| Instruction::BVS
+			| Instruction::BCC
+			| Instruction::BCS
+			| Instruction::BEQ
+			| Instruction::BGE
+			| Instruction::BGT
+			| Instruction::BHI => 4,
+
+			Instruction::JMP(adr) => match adr {
+				AddrTypeFive::Addr => 2,
+				AddrTypeFive::nX | AddrTypeFive::nY | AddrTypeFive::AX | AddrTypeFive::AY => 4,
+			},
+			Instruction::JSR(adr) => match adr {
+				AddrTypeFive::Addr => 4,
+				AddrTypeFive::nX | AddrTypeFive::nY | AddrTypeFive::AX | AddrTypeFive::AY => 5,
+			},
+
+			Instruction::RTS => 2,
+			Instruction::RTI => 6,
+
+			Instruction::NOP | Instruction::TSTA | Instruction::TFR(_) => 2,
+
+			Instruction::ASLA
+			| Instruction::ASRA
+			| Instruction::CLRA
+			| Instruction::COMA
+			| Instruction::DECA
+			| Instruction::INCA
+			| Instruction::LSRA
+			| Instruction::NEGA
+			| Instruction::ROLA
+			| Instruction::RORA
+			| Instruction::PSHA
+			| Instruction::PSHX
+			| Instruction::PSHY
+			| Instruction::PSHCC
+			| Instruction::PULA
+			| Instruction::PULX
+			| Instruction::PULY
+			| Instruction::PULCC => 3,
+
+			Instruction::ANDCC
+			| Instruction::ORCC
+			| Instruction::EXG(_)
+			| Instruction::LEAX(_)
+			| Instruction::LEAY(_)
+			| Instruction::LEASP(_) => 4,
+
+			Instruction::CLR(_)
+			| Instruction::TST(_)
+			| Instruction::STX(_)
+			| Instruction::STY(_)
+			| Instruction::STSP(_) => 3,
+
+			Instruction::ASL(_)
+			| Instruction::ASR(_)
+			| Instruction::COM(_)
+			| Instruction::DEC(_)
+			| Instruction::INC(_)
+			| Instruction::LSR(_)
+			| Instruction::NEG(_)
+			| Instruction::ROL(_)
+			| Instruction::ROR(_) => 4,
+
+			Instruction::LDX(adr) | Instruction::LDY(adr) | Instruction::LDSP(adr) => match adr {
+				AddrTypeTwo::Data => 2,
+				AddrTypeTwo::Addr | AddrTypeTwo::nSP | AddrTypeTwo::nX | AddrTypeTwo::nY => 3,
+			},
+
+			Instruction::ADCA(adr)
+			| Instruction::ADDA(adr)
+			| Instruction::ANDA(adr)
+			| Instruction::EORA(adr)
+			| Instruction::ORA(adr)
+			| Instruction::SBCA(adr)
+			| Instruction::SUBA(adr) => match adr {
+				AddrTypeTwo::Data => 4,
+				AddrTypeTwo::Addr | AddrTypeTwo::nSP | AddrTypeTwo::nX | AddrTypeTwo::nY => 5,
+			},
+
+			Instruction::BITA(adr) | Instruction::CMPA(adr) => match adr {
+				AddrTypeTwo::Data => 3,
+				AddrTypeTwo::Addr | AddrTypeTwo::nSP | AddrTypeTwo::nX | AddrTypeTwo::nY => 4,
+			},
+			Instruction::CMPX(adr) | Instruction::CMPY(adr) => match adr {
+				AddrTypeFour::Data => 3,
+				AddrTypeFour::Addr | AddrTypeFour::nSP => 4,
+			},
+			Instruction::CMPSP(adr) => match adr {
+				CmpspAddr::Data => 3,
+				CmpspAddr::Addr => 4,
+			},
+
+			Instruction::STA(adr) => match adr {
+				StaAddr::Addr
+				| StaAddr::nSP
+				| StaAddr::nX
+				| StaAddr::AX
+				| StaAddr::nY
+				| StaAddr::AY => 3,
+				StaAddr::Xplus
+				| StaAddr::Xminus
+				| StaAddr::plusX
+				| StaAddr::minusX
+				| StaAddr::Yplus
+				| StaAddr::Yminus
+				| StaAddr::plusY
+				| StaAddr::minusY => 4,
+			},
+			Instruction::LDA(adr) => match adr {
+				LdaAddr::Data => 2,
+				LdaAddr::Addr
+				| LdaAddr::nSP
+				| LdaAddr::nX
+				| LdaAddr::AX
+				| LdaAddr::nY
+				| LdaAddr::AY => 3,
+				LdaAddr::Xplus
+				| LdaAddr::Xminus
+				| LdaAddr::plusX
+				| LdaAddr::minusX
+				| LdaAddr::Yplus
+				| LdaAddr::Yminus
+				| LdaAddr::plusY
+				| LdaAddr::minusY => 4,
+			},
+		}
+	}
+}
+
+/// An instruction read from memory, with its operand byte
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DecodedInstruction {
+	pub inst: Instruction,
+	/// The byte after the opcode, for instructions that have one
+	pub operand: Option<u8>,
+	pub size: u8,
+	/// Clock cycles, counting branches as taken
+	pub cycles: u8,
+	pub addr: u8,
+}
+
+/// Decodes the instruction at `addr` without side effects on the bus
+pub fn decode<B: Bus>(mem: &B, addr: u8) -> Result<DecodedInstruction> {
+	DecodedInstruction::fetch(addr, |adr| mem.peek(adr))
+}
+
+impl DecodedInstruction {
+	/// Decodes the instruction at `addr`, reading its bytes with `read`
+	pub(crate) fn fetch(addr: u8, mut read: impl FnMut(u8) -> u8) -> Result<Self> {
+		let inst = Instruction::try_from(read(addr))?;
+		let operand = if inst.size() > 1 {
+			Some(read(addr.wrapping_add(1)))
+		} else {
+			None
+		};
+		Ok(DecodedInstruction {
+			inst,
+			operand,
+			size: inst.size(),
+			cycles: inst.cycles(),
+			addr,
+		})
+	}
+
+	/// Where control goes if a branch is taken, or a `JMP`/`JSR` to a fixed address
+	pub fn target(&self) -> Option<u8> {
+		let operand = self.operand?;
+		match self.inst {
+			Instruction::JMP(AddrTypeFive::Addr) | Instruction::JSR(AddrTypeFive::Addr) =>
+				Some(operand),
+			inst if inst.is_branch() =>
+				Some(self.addr.wrapping_add(self.size).wrapping_add(operand)),
+			_ => None,
+		}
+	}
+
+	fn write_to<T: fmt::Write>(&self, out: &mut T) -> Result<()> {
+		let next = self.operand.unwrap_or(0);
+		match self.inst {
+			Instruction::ADCA(adr) => {
+				write!(out, "ADCA    ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::ADDA(adr) => {
+				write!(out, "ADDA    ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::ANDA(adr) => {
+				write!(out, "ANDA    ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::ANDCC => {
+				write!(out, "ANDCC   #${:02X}", next)?;
+			}
+			Instruction::ASLA => {
+				write!(out, "ASLA    ")?;
+			}
+			Instruction::ASL(adr) => {
+				write!(out, "ASL     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::ASRA => {
+				write!(out, "ASRA    ")?;
+			}
+			Instruction::ASR(adr) => {
+				write!(out, "ASR     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::BITA(adr) => {
+				write!(out, "BITA    ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::BLE => {
+				write!(out, "BLE     ${:02X}", next)?;
+			}
+			Instruction::BLS => {
+				write!(out, "BLS     ${:02X}", next)?;
+			}
+			Instruction::BLT => {
+				write!(out, "BLT     ${:02X}", next)?;
+			}
+			Instruction::BMI => {
+				write!(out, "BMI     ${:02X}", next)?;
+			}
+			Instruction::BNE => {
+				write!(out, "BNE     ${:02X}", next)?;
+			}
+			Instruction::BPL => {
+				write!(out, "BPL     ${:02X}", next)?;
+			}
+			Instruction::BRA => {
+				write!(out, "BRA     ${:02X}", next)?;
+			}
+			Instruction::BSR => {
+				write!(out, "BSR     ${:02X}", next)?;
+			}
+			Instruction::BVC => {
+				write!(out, "BVC     ${:02X}", next)?;
+			}
+			Instruction::BVS => {
+				write!(out, "BVS     ${:02X}", next)?;
+			}
+			Instruction::BCC => {
+				write!(out, "BCC     ${:02X}", next)?;
+			}
+			Instruction::BCS => {
+				write!(out, "BCS     ${:02X}", next)?;
+			}
+			Instruction::BEQ => {
+				write!(out, "BEQ     ${:02X}", next)?;
+			}
+			Instruction::BGE => {
+				write!(out, "BGE     ${:02X}", next)?;
+			}
+			Instruction::BGT => {
+				write!(out, "BGT     ${:02X}", next)?;
+			}
+			Instruction::BHI => {
+				write!(out, "BHI     ${:02X}", next)?;
+			}
+			Instruction::CLRA => {
+				write!(out, "CLRA")?;
+			}
+			Instruction::CLR(adr) => {
+				write!(out, "CLR     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::CMPA(adr) => {
+				write!(out, "CMPA    ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::CMPX(adr) => {
+				write!(out, "CMPX    ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::CMPY(adr) => {
+				write!(out, "CMPY    ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::CMPSP(adr) => match adr {
+				CmpspAddr::Data => write!(out, "CMPSP   #${:02X}", next)?,
+				CmpspAddr::Addr => write!(out, "CMPSP   ${:02X}", next)?,
+			},
+			Instruction::COMA => {
+				write!(out, "COMA")?;
+			}
+			Instruction::COM(adr) => {
+				write!(out, "COM     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::DECA => {
+				write!(out, "DECA")?;
+			}
+			Instruction::DEC(adr) => {
+				write!(out, "DEC     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::EORA(adr) => {
+				write!(out, "EORA    ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::EXG(adr) => match adr {
+				ExgAddr::XY => write!(out, "EXG     X,Y")?,
+				ExgAddr::ACC => write!(out, "EXG     A,CC")?,
+				ExgAddr::XSP => write!(out, "EXG     X,SP")?,
+				ExgAddr::YSP => write!(out, "EXG     Y,SP")?,
+			},
+			Instruction::INCA => {
+				write!(out, "INCA")?;
+			}
+			Instruction::INC(adr) => {
+				write!(out, "INC     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::JMP(adr) => {
+				write!(out, "JMP     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::JSR(adr) => {
+				write!(out, "JSR     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::LDA(adr) => match adr {
+				LdaAddr::Data => write!(out, "LDA     #${:02X}", next)?,
+				LdaAddr::Addr => write!(out, "LDA     ${:02X}", next)?,
+				LdaAddr::nSP => write!(out, "LDA     ${:02X},SP", next)?,
+				LdaAddr::nX => write!(out, "LDA     ${:02X},X", next)?,
+				LdaAddr::AX => write!(out, "LDA     A,X")?,
+				LdaAddr::Xplus => write!(out, "LDA     X+")?,
+				LdaAddr::Xminus => write!(out, "LDA     X-")?,
+				LdaAddr::plusX => write!(out, "LDA     +X")?,
+				LdaAddr::minusX => write!(out, "LDA     -X")?,
+				LdaAddr::nY => write!(out, "LDA     ${:02X},Y", next)?,
+				LdaAddr::AY => write!(out, "LDA     A,Y")?,
+				LdaAddr::Yplus => write!(out, "LDA     Y+")?,
+				LdaAddr::Yminus => write!(out, "LDA     Y-")?,
+				LdaAddr::plusY => write!(out, "LDA     +Y")?,
+				LdaAddr::minusY => write!(out, "LDA     -Y")?,
+			},
+			Instruction::LDX(adr) => {
+				write!(out, "LDX     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::LDY(adr) => {
+				write!(out, "LDY     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::LDSP(adr) => {
+				write!(out, "LDSP    ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::LEAX(adr) => match adr {
+				LeaxAddr::nX => write!(out, "LEAX    ${:02X},X", next)?,
+				LeaxAddr::nSP => write!(out, "LEAX    ${:02X},SP", next)?,
+			},
+			Instruction::LEAY(adr) => match adr {
+				LeayAddr::nY => write!(out, "LEAY    ${:02X},Y", next)?,
+				LeayAddr::nSP => write!(out, "LEAY    ${:02X},SP", next)?,
+			},
+			Instruction::LEASP(adr) => match adr {
+				LeaspAddr::nX => write!(out, "LEASP   ${:02X},X", next)?,
+				LeaspAddr::nY => write!(out, "LEASP   ${:02X},Y", next)?,
+				LeaspAddr::nSP => write!(out, "LEASP   ${:02X},SP", next)?,
+			},
+			Instruction::LSRA => {
+				write!(out, "LSRA")?;
+			}
+			Instruction::LSR(adr) => {
+				write!(out, "LSR     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::NEGA => {
+				write!(out, "NEGA")?;
+			}
+			Instruction::NEG(adr) => {
+				write!(out, "NEG     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::NOP => {
+				write!(out, "NOP")?;
+			}
+			Instruction::ORA(adr) => {
+				write!(out, "ORA     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::ORCC => {
+				write!(out, "ORCC    #${:02X}", next)?;
+			}
+			Instruction::PSHA => {
+				write!(out, "PSHA")?;
+			}
+			Instruction::PSHX => {
+				write!(out, "PSHX")?;
+			}
+			Instruction::PSHY => {
+				write!(out, "PSHY")?;
+			}
+			Instruction::PSHCC => {
+				write!(out, "PSHCC")?;
+			}
+			Instruction::PULA => {
+				write!(out, "PULA")?;
+			}
+			Instruction::PULX => {
+				write!(out, "PULX")?;
+			}
+			Instruction::PULY => {
+				write!(out, "PULY")?;
+			}
+			Instruction::PULCC => {
+				write!(out, "PULCC")?;
+			}
+			Instruction::ROLA => {
+				write!(out, "ROLA")?;
+			}
+			Instruction::ROL(adr) => {
+				write!(out, "ROL     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::RORA => {
+				write!(out, "RORA")?;
+			}
+			Instruction::ROR(adr) => {
+				write!(out, "ROR     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::RTS => {
+				write!(out, "RTS")?;
+			}
+			Instruction::RTI => {
+				write!(out, "RTI")?;
+			}
+			Instruction::SBCA(adr) => {
+				write!(out, "SBCA    ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::STA(adr) => match adr {
+				StaAddr::Addr => write!(out, "STA     ${:02X}", next)?,
+				StaAddr::nSP => write!(out, "STA     ${:02X},SP", next)?,
+				StaAddr::nX => write!(out, "STA     ${:02X},X", next)?,
+				StaAddr::AX => write!(out, "STA     A,X")?,
+				StaAddr::Xplus => write!(out, "STA     X+")?,
+				StaAddr::Xminus => write!(out, "STA     X-")?,
+				StaAddr::plusX => write!(out, "STA     +X")?,
+				StaAddr::minusX => write!(out, "STA     -X")?,
+				StaAddr::nY => write!(out, "STA     ${:02X},Y", next)?,
+				StaAddr::AY => write!(out, "STA     A,Y")?,
+				StaAddr::Yplus => write!(out, "STA     Y+")?,
+				StaAddr::Yminus => write!(out, "STA     Y-")?,
+				StaAddr::plusY => write!(out, "STA     +Y")?,
+				StaAddr::minusY => write!(out, "STA     -Y")?,
+			},
+			Instruction::STX(adr) => {
+				write!(out, "STX     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::STY(adr) => {
+				write!(out, "STY     ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::STSP(adr) => {
+				write!(out, "STSP    ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::SUBA(adr) => {
+				write!(out, "SUBA    ")?;
+				adr.write_with_next(out, next)?;
+			}
+			Instruction::TFR(adr) => match adr {
+				TfrAddr::ACC => write!(out, "TFR     A,CC")?,
+				TfrAddr::CCA => write!(out, "TFR     CC,A")?,
+				TfrAddr::XY => write!(out, "TFR     X,Y")?,
+				TfrAddr::YX => write!(out, "TFR     Y,X")?,
+				TfrAddr::XSP => write!(out, "TFR     X,SP")?,
+				TfrAddr::SPX => write!(out, "TFR     SP,X")?,
+				TfrAddr::YSP => write!(out, "TFR     Y,SP")?,
+				TfrAddr::SPY => write!(out, "TFR     SP,Y")?,
+			},
+			Instruction::TSTA => {
+				write!(out, "TSTA")?;
+			}
+			Instruction::TST(adr) => {
+				write!(out, "TST     ")?;
+				adr.write_with_next(out, next)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+impl fmt::Display for DecodedInstruction {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.write_to(f).map_err(|_| fmt::Error)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn decode_operands() {
+		let mut mem = [0; 256];
+		mem[0x10..0x15].copy_from_slice(&[0xF0, 0x05, 0x08, 0x25, 0xFB]);
+		let lda = decode(&mem, 0x10).unwrap();
+		assert_eq!(
+			lda,
+			DecodedInstruction {
+				inst: Instruction::LDA(LdaAddr::Data),
+				operand: Some(0x05),
+				size: 2,
+				cycles: 2,
+				addr: 0x10,
+			}
+		);
+		assert_eq!(lda.to_string(), "LDA     #$05");
+		assert_eq!(lda.target(), None);
+
+		let deca = decode(&mem, 0x12).unwrap();
+		assert_eq!((deca.operand, deca.size), (None, 1));
+
+		let bne = decode(&mem, 0x13).unwrap();
+		assert_eq!(bne.target(), Some(0x10));
+		assert_eq!(bne.to_string(), "BNE     $FB");
+
+		// Every mnemonic is padded to the same width, so the operands line up
+		mem[0x30..0x34].copy_from_slice(&[0xBE, 0x01, 0x32, 0x50]);
+		assert_eq!(decode(&mem, 0x30).unwrap().to_string(), "LEASP   $01,SP");
+		assert_eq!(decode(&mem, 0x32).unwrap().to_string(), "STSP    $50");
+
+		mem[0x20] = 0x03;
+		assert!(decode(&mem, 0x20).is_err());
+	}
 }
@@ -0,0 +1,194 @@
+use crate::{stepper::Stepper, timer::Timer};
+
+/// Set in a keypad's value from a key press until the processor reads the port
+pub const KEY_READY: u8 = 0x80;
+
+/// Devices that can be attached to the I/O ports of the DigiFlisp board
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum IoDevice {
+	Nothing,
+	Bargraph(u8),
+	HexDisplay(u8),
+	SevenSeg(u8),
+	StepperMotor(Stepper),
+	DILSwitch(u8),
+	KeyPad(u8),
+	IRQFlipFlop(u8),
+	Timer(Timer),
+}
+
+impl IoDevice {
+	/// The value seen by the processor when reading the port. `None` if nothing is attached
+	pub fn read(&self) -> Option<u8> {
+		let res = match self {
+			IoDevice::Nothing => return None,
+			IoDevice::Bargraph(_) => 0,
+			IoDevice::HexDisplay(stored) => *stored,
+			IoDevice::SevenSeg(stored) => *stored,
+			IoDevice::StepperMotor(motor) => motor.coils,
+			IoDevice::DILSwitch(stored) => *stored,
+			IoDevice::KeyPad(stored) => *stored,
+			IoDevice::IRQFlipFlop(stored) => *stored,
+			IoDevice::Timer(timer) => timer.status(),
+		};
+		Some(res)
+	}
+
+	/// Latches a value written by the processor. Input devices ignore writes and the IRQ
+	/// flip-flop is cleared by them. Returns false if nothing is attached
+	pub fn write(&mut self, val: u8) -> bool {
+		match self {
+			IoDevice::Nothing => return false,
+			IoDevice::Bargraph(stored)
+			| IoDevice::HexDisplay(stored)
+			| IoDevice::SevenSeg(stored) => *stored = val,
+			// Any write acknowledges the interrupt
+			IoDevice::IRQFlipFlop(stored) => *stored = 0,
+			IoDevice::StepperMotor(motor) => motor.write(val),
+			IoDevice::Timer(timer) => timer.write(val),
+			IoDevice::DILSwitch(_) | IoDevice::KeyPad(_) => {}
+		}
+		true
+	}
+
+	/// Called after the processor has read the port. Reading a keypad acknowledges the key
+	/// press, clearing its ready bit
+	pub fn acknowledge(&mut self) {
+		if let IoDevice::KeyPad(stored) = self {
+			*stored &= !KEY_READY;
+		}
+	}
+
+	/// Whether the value can change from the outside, without the processor writing it
+	pub fn is_input(&self) -> bool {
+		matches!(
+			self,
+			IoDevice::DILSwitch(_)
+				| IoDevice::KeyPad(_)
+				| IoDevice::IRQFlipFlop(_)
+				| IoDevice::Timer(_)
+		)
+	}
+
+	/// Whether the device is holding the interrupt line, a set IRQ flip-flop or a timer whose
+	/// period has elapsed with interrupts enabled
+	pub fn irq(&self) -> bool {
+		match self {
+			IoDevice::IRQFlipFlop(stored) => *stored != 0,
+			IoDevice::Timer(timer) => timer.irq(),
+			_ => false,
+		}
+	}
+
+	/// Lets `cycles` clock cycles pass for a timer. Other devices are left alone
+	pub fn tick(&mut self, cycles: u8) {
+		if let IoDevice::Timer(timer) = self {
+			timer.tick(cycles);
+		}
+	}
+
+	/// Sets an IRQ flip-flop, which interrupts the processor until a write to the port clears
+	/// it. Other devices are left alone
+	pub fn trigger(&mut self) {
+		if let IoDevice::IRQFlipFlop(stored) = self {
+			*stored = 1;
+		}
+	}
+
+	/// Flips switch `bit` of a DIL switch. Other devices are left alone
+	pub fn toggle_switch(&mut self, bit: u8) {
+		if let IoDevice::DILSwitch(stored) = self {
+			*stored ^= 1 << (bit & 7);
+		}
+	}
+
+	/// Enters the hex digit `key` on a keypad and raises its ready bit. Other devices are left
+	/// alone
+	pub fn press_key(&mut self, key: u8) {
+		if let IoDevice::KeyPad(stored) = self {
+			*stored = KEY_READY | (key & 0x0F);
+		}
+	}
+
+	/// Clears the device's value, keeping it attached
+	pub fn reset(&mut self) {
+		if let IoDevice::StepperMotor(motor) = self {
+			*motor = Stepper::default();
+		} else if let IoDevice::Timer(timer) = self {
+			*timer = Timer::default();
+		} else if let Some(stored) = self.stored_mut() {
+			*stored = 0;
+		}
+	}
+
+	/// Name used in commands and snapshots
+	pub fn name(&self) -> &'static str {
+		match self {
+			IoDevice::Nothing => "nothing",
+			IoDevice::Bargraph(_) => "bargraph",
+			IoDevice::HexDisplay(_) => "hexdisplay",
+			IoDevice::SevenSeg(_) => "sevenseg",
+			IoDevice::StepperMotor(_) => "steppermotor",
+			IoDevice::DILSwitch(_) => "dilswitch",
+			IoDevice::KeyPad(_) => "keypad",
+			IoDevice::IRQFlipFlop(_) => "irqflipflop",
+			IoDevice::Timer(_) => "timer",
+		}
+	}
+
+	/// The device called `name`, ignoring case, with its value cleared
+	pub fn from_name(name: &str) -> Option<Self> {
+		let res = match name.to_ascii_lowercase().as_str() {
+			"nothing" => IoDevice::Nothing,
+			"bargraph" => IoDevice::Bargraph(0),
+			"hexdisplay" => IoDevice::HexDisplay(0),
+			"sevenseg" => IoDevice::SevenSeg(0),
+			"steppermotor" => IoDevice::StepperMotor(Stepper::default()),
+			"dilswitch" => IoDevice::DILSwitch(0),
+			"keypad" => IoDevice::KeyPad(0),
+			"irqflipflop" => IoDevice::IRQFlipFlop(0),
+			"timer" => IoDevice::Timer(Timer::default()),
+			_ => return None,
+		};
+		Some(res)
+	}
+
+	/// The value the device holds, unlike [`read`](IoDevice::read) this includes what was
+	/// written to output devices
+	pub fn value(&self) -> Option<u8> {
+		match self {
+			IoDevice::Nothing => None,
+			IoDevice::Bargraph(stored)
+			| IoDevice::HexDisplay(stored)
+			| IoDevice::SevenSeg(stored)
+			| IoDevice::DILSwitch(stored)
+			| IoDevice::KeyPad(stored)
+			| IoDevice::IRQFlipFlop(stored) => Some(*stored),
+			IoDevice::StepperMotor(motor) => Some(motor.coils),
+			IoDevice::Timer(timer) => Some(timer.control),
+		}
+	}
+
+	/// Sets the value the device holds, as if it had been written or input. A stepper motor's
+	/// coils are set without turning the shaft, and a timer's control byte without restarting
+	/// it. Does nothing if nothing is attached
+	pub fn set_value(&mut self, val: u8) {
+		if let Some(stored) = self.stored_mut() {
+			*stored = val;
+		}
+	}
+
+	fn stored_mut(&mut self) -> Option<&mut u8> {
+		match self {
+			IoDevice::Nothing => None,
+			IoDevice::Bargraph(stored)
+			| IoDevice::HexDisplay(stored)
+			| IoDevice::SevenSeg(stored)
+			| IoDevice::DILSwitch(stored)
+			| IoDevice::KeyPad(stored)
+			| IoDevice::IRQFlipFlop(stored) => Some(stored),
+			IoDevice::StepperMotor(motor) => Some(&mut motor.coils),
+			IoDevice::Timer(timer) => Some(&mut timer.control),
+		}
+	}
+}
@@ -1,10 +1,37 @@
 pub mod addressing;
+pub mod assembler;
+pub mod board;
+pub mod breakpoint;
+pub mod bus;
+pub mod disassembler;
 pub mod error;
+pub mod history;
+pub mod ihex;
 pub mod instructions;
+pub mod io_device;
 pub mod processor;
+pub mod run;
+pub mod snapshot;
+pub mod srecord;
+pub mod stepper;
+pub mod timer;
+pub mod trace;
+pub mod watchpoint;
 
 use addressing::*;
-pub use error::FlispError;
+pub use assembler::assemble;
+pub use board::Board;
+pub use breakpoint::Breakpoints;
+pub use bus::{Access, AccessKind, Bus};
+pub use disassembler::disassemble;
 use error::Result;
-pub use instructions::Instruction;
-pub use processor::Flisp;
+pub use error::{Diagnostic, FlispError, RecordProblem};
+pub use history::History;
+pub use instructions::{decode, DecodedInstruction, Instruction};
+pub use io_device::{IoDevice, KEY_READY};
+pub use processor::{Flisp, IllegalOpcode, IllegalOpcodePolicy};
+pub use run::{Limits, LoopDetector, StopReason};
+pub use stepper::Stepper;
+pub use timer::Timer;
+pub use trace::Trace;
+pub use watchpoint::Watchpoints;
@@ -1,15 +1,18 @@
 use std::{
-	convert::{TryFrom, TryInto},
-	fmt::Write,
+	fmt::{self, Write},
 	mem, result,
 	str::FromStr,
 };
 
-use crate::*;
+use crate::{
+	run::{Limits, LoopDetector, StopReason},
+	watchpoint::WatchHit,
+	*,
+};
 
 #[allow(non_snake_case, non_camel_case_types)]
-#[derive(Debug, Clone, PartialEq)]
-pub struct Flisp {
+#[derive(Debug, Clone)]
+pub struct Flisp<B: Bus = [u8; 256]> {
 	pub A: u8,
 	pub Y: u8,
 	pub X: u8,
@@ -17,10 +20,75 @@ pub struct Flisp {
 	pub CC: u8,
 	pub SP: u8,
 	pub PC: u8,
-	pub mem: [u8; 256],
+	pub mem: B,
+	/// Clock cycles executed so far
+	pub cycles: u64,
+	/// Pending interrupt request, serviced before the next instruction once the I flag is clear
+	pub irq: bool,
+	/// Memory accesses made by the last step, in order
+	pub accesses: Vec<Access>,
+	/// What to do when an opcode isn't an instruction
+	pub on_illegal_opcode: IllegalOpcodePolicy,
+	/// The illegal opcode the last step ran into, if any
+	pub illegal_opcode: Option<IllegalOpcode>,
+}
+
+/// What the processor does when it fetches a byte that isn't an instruction
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum IllegalOpcodePolicy {
+	/// Jumps through the vector at $FD, like the real processor
+	#[default]
+	Trap,
+	/// Stays at the opcode, [`run`](Flisp::run) stops with
+	/// [`StopReason::IllegalOpcode`]
+	Halt,
+	/// Panics, to catch runaway programs in tests
+	Panic,
+}
+
+/// A byte fetched as an opcode that isn't one
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct IllegalOpcode {
+	pub addr: u8,
+	pub byte: u8,
 }
 
-impl Flisp {
+impl fmt::Display for IllegalOpcode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Illegal opcode ${:02X} at ${:02X}", self.byte, self.addr)
+	}
+}
+
+/// Compares the machine state. The accesses and illegal opcode of the last step and the policy
+/// aren't part of it
+impl<B: Bus + PartialEq> PartialEq for Flisp<B> {
+	fn eq(&self, other: &Self) -> bool {
+		self.A == other.A
+			&& self.Y == other.Y
+			&& self.X == other.X
+			&& self.CC == other.CC
+			&& self.SP == other.SP
+			&& self.PC == other.PC
+			&& self.mem == other.mem
+			&& self.cycles == other.cycles
+			&& self.irq == other.irq
+	}
+}
+
+/// The trap isn't listed in the reference, count it as a fetch of the opcode and the vector
+const TRAP_CYCLES: u8 = 2;
+/// Neither is the interrupt sequence, count it as the five pushes and the vector fetch
+const IRQ_CYCLES: u8 = 6;
+
+impl<B: Bus> Flisp<B> {
+	fn set_i(&mut self, to: bool) {
+		self.CC = (self.CC & !(1 << 4)) | ((to as u8) << 4);
+	}
+
+	fn get_i(&self) -> bool {
+		self.CC & (1 << 4) != 0
+	}
+
 	fn set_n(&mut self, to: bool) {
 		self.CC = (self.CC & !(1 << 3)) | ((to as u8) << 3);
 	}
@@ -61,6 +129,34 @@ impl Flisp {
 		self.CC & 1 != 0
 	}
 
+	/// Reads through the bus and records the access
+	pub(crate) fn read(&mut self, adr: u8) -> u8 {
+		let value = self.mem.read(adr);
+		self.accesses.push(Access {
+			adr,
+			kind: AccessKind::Read,
+			value,
+		});
+		value
+	}
+
+	/// Writes through the bus and records the access along with the value it replaced
+	pub(crate) fn write(&mut self, adr: u8, value: u8) {
+		let old = self.mem.peek(adr);
+		self.mem.write(adr, value);
+		self.accesses.push(Access {
+			adr,
+			kind: AccessKind::Write { old },
+			value,
+		});
+	}
+
+	fn modify(&mut self, adr: u8, op: fn(&mut Self, u8) -> u8) {
+		let data = self.read(adr);
+		let res = op(self, data);
+		self.write(adr, res);
+	}
+
 	fn add(&mut self, data: u8) {
 		let a_before = self.A as i8;
 		let (res, carry) = self.A.overflowing_add(data);
@@ -207,25 +303,230 @@ impl Flisp {
 		self.set_c(false);
 	}
 
-	pub fn step(&mut self) {
-		let inst = if let Ok(inst) = Instruction::try_from(self.mem[self.PC as usize]) {
-			inst
+	/// Connects a processor to `mem` and resets it
+	pub fn new(mem: B) -> Self {
+		let mut flisp = Flisp {
+			A: 0,
+			Y: 0,
+			X: 0,
+			CC: 0,
+			SP: 0,
+			PC: 0,
+			mem,
+			cycles: 0,
+			irq: false,
+			accesses: Vec::new(),
+			on_illegal_opcode: IllegalOpcodePolicy::default(),
+			illegal_opcode: None,
+		};
+		flisp.reset();
+		flisp
+	}
+
+	/// Runs the reset sequence: clears the registers and any pending interrupt, masks
+	/// interrupts and loads PC from the reset vector at $FF. Memory is left untouched
+	pub fn reset(&mut self) {
+		self.A = 0;
+		self.X = 0;
+		self.Y = 0;
+		self.SP = 0;
+		self.CC = 0;
+		self.set_i(true);
+		self.irq = false;
+		self.cycles = 0;
+		self.PC = self.mem.read(0xFF);
+	}
+
+	/// Like [`reset`](Flisp::reset), but everything on the bus is returned to its power on
+	/// state first
+	pub fn power_on(&mut self) {
+		self.mem.power_on();
+		self.reset();
+	}
+
+	/// Whether the next step services an interrupt instead of executing an instruction, because
+	/// of [`raise_irq`](Flisp::raise_irq) or a device holding the interrupt line
+	pub fn irq_pending(&self) -> bool {
+		(self.irq || self.mem.irq()) && !self.get_i()
+	}
+
+	/// Executes one instruction and returns the number of clock cycles it took. The memory
+	/// accesses it made are left in `accesses`, and an opcode that isn't an instruction in
+	/// `illegal_opcode`
+	pub fn step(&mut self) -> u8 {
+		self.accesses.clear();
+		self.illegal_opcode = None;
+		let cycles = if self.irq_pending() {
+			self.interrupt()
+		} else {
+			self.execute()
+		};
+		self.cycles += u64::from(cycles);
+		self.mem.tick(cycles);
+		cycles
+	}
+
+	/// Steps until at least `cycles` clock cycles have passed and returns the amount actually
+	/// spent, which may overshoot by the length of the last instruction. Stops early if an
+	/// illegal opcode halts the processor
+	pub fn run_for_cycles(&mut self, cycles: u64) -> u64 {
+		let mut spent = 0;
+		while spent < cycles {
+			spent += u64::from(self.step());
+			if self.halted_on_illegal().is_some() {
+				break;
+			}
+		}
+		spent
+	}
+
+	/// Steps until PC reaches a breakpoint whose condition holds, or until `max_cycles` have
+	/// passed. Always executes at least one instruction, so it can be used to continue from a
+	/// breakpoint. Returns the address of the breakpoint, or `None` if it ran out of cycles or
+	/// an illegal opcode halted the processor
+	pub fn run_until_break(&mut self, breakpoints: &Breakpoints, max_cycles: u64) -> Option<u8> {
+		let mut spent = 0;
+		loop {
+			spent += u64::from(self.step());
+			if self.halted_on_illegal().is_some() {
+				return None;
+			}
+			if breakpoints.hit(self) {
+				return Some(self.PC);
+			}
+			if spent >= max_cycles {
+				return None;
+			}
+		}
+	}
+
+	/// The illegal opcode the last step stopped at, when the policy is to halt on them
+	fn halted_on_illegal(&self) -> Option<IllegalOpcode> {
+		self.illegal_opcode
+			.filter(|_| self.on_illegal_opcode == IllegalOpcodePolicy::Halt)
+	}
+
+	/// Whether PC is at a branch or jump to itself with interrupts masked, so nothing can ever
+	/// happen again
+	pub fn halted(&self) -> bool {
+		let self_loop = match decode(&self.mem, self.PC) {
+			Ok(decoded) => match decoded.inst {
+				Instruction::BRA | Instruction::JMP(AddrTypeFive::Addr) =>
+					decoded.target() == Some(self.PC),
+				_ => false,
+			},
+			Err(_) => false,
+		};
+		self_loop && self.get_i()
+	}
+
+	/// Steps until a breakpoint is reached, the program halts or loops forever according to
+	/// `detector`, an illegal opcode halts it, or a limit runs out. Checks before every step,
+	/// so it doesn't move if it is already at a breakpoint. Returns the reason along with the
+	/// number of steps taken
+	pub fn run(
+		&mut self,
+		breakpoints: &Breakpoints,
+		detector: &mut LoopDetector<B>,
+		limits: Limits,
+	) -> (StopReason, u64)
+	where
+		B: Clone + PartialEq,
+	{
+		let start = self.cycles;
+		let mut steps = 0;
+		loop {
+			if breakpoints.hit(self) {
+				return (StopReason::Breakpoint(self.PC), steps);
+			}
+			if let Some(reason) = detector.check(self) {
+				return (reason, steps);
+			}
+			if matches!(limits.max_steps, Some(max) if steps >= max) {
+				return (StopReason::StepLimit, steps);
+			}
+			if matches!(limits.max_cycles, Some(max) if self.cycles - start >= max) {
+				return (StopReason::CycleLimit, steps);
+			}
+			self.step();
+			steps += 1;
+			if let Some(IllegalOpcode { addr, byte }) = self.halted_on_illegal() {
+				return (StopReason::IllegalOpcode { addr, byte }, steps);
+			}
+		}
+	}
+
+	/// Steps and returns every watchpoint the instruction triggered
+	pub fn step_watched(&mut self, watchpoints: &Watchpoints) -> Vec<WatchHit> {
+		let pc = self.PC;
+		self.step();
+		watchpoints.check(pc, &self.accesses)
+	}
+
+	/// Requests an interrupt. It stays pending until the I flag is cleared and it can be serviced
+	pub fn raise_irq(&mut self) {
+		self.irq = true;
+	}
+
+	/// Pushes the state in the order RTI pops it and jumps through the interrupt vector
+	fn interrupt(&mut self) -> u8 {
+		self.irq = false;
+		for val in [self.PC, self.Y, self.X, self.A, self.CC].iter() {
+			self.SP = self.SP.wrapping_sub(1);
+			self.write(self.SP, *val);
+		}
+		self.set_i(true);
+		self.PC = self.read(0xFD);
+		IRQ_CYCLES
+	}
+
+	/// Handles the opcode at PC according to `on_illegal_opcode`
+	fn illegal(&mut self) -> u8 {
+		let illegal = IllegalOpcode {
+			addr: self.PC,
+			byte: self.mem.peek(self.PC),
+		};
+		self.illegal_opcode = Some(illegal);
+		match self.on_illegal_opcode {
+			IllegalOpcodePolicy::Trap => {
+				self.PC = self.read(0xFD);
+				TRAP_CYCLES
+			}
+			IllegalOpcodePolicy::Halt => 0,
+			IllegalOpcodePolicy::Panic => panic!("{}", illegal),
+		}
+	}
+
+	fn execute(&mut self) -> u8 {
+		let (bus, accesses) = (&mut self.mem, &mut self.accesses);
+		let fetch = |adr| {
+			let value = bus.read(adr);
+			accesses.push(Access {
+				adr,
+				kind: AccessKind::Fetch,
+				value,
+			});
+			value
+		};
+		let decoded = if let Ok(decoded) = DecodedInstruction::fetch(self.PC, fetch) {
+			decoded
 		} else {
-			self.PC = self.mem[0xFD];
-			return;
+			return self.illegal();
 		};
-		let n = self.mem[self.PC.wrapping_add(1) as usize];
+		let inst = decoded.inst;
+		let cycles = decoded.cycles;
+		let n = decoded.operand.unwrap_or(0);
 		match inst {
 			Instruction::ADCA(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.add(rhs + self.get_c() as u8);
 			}
 			Instruction::ADDA(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.add(rhs);
 			}
 			Instruction::ANDA(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.A = self.and(rhs);
 			}
 			Instruction::ANDCC => {
@@ -236,124 +537,125 @@ impl Flisp {
 				self.A = self.asl(self.A);
 			}
 			Instruction::ASL(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.mem[idx] = self.asl(self.mem[idx]);
+				let idx = adr.get_index(self, n);
+				self.modify(idx, Self::asl);
 			}
 			Instruction::ASRA => {
 				self.A = self.asr(self.A);
 			}
 			Instruction::ASR(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.mem[idx] = self.asr(self.mem[idx]);
+				let idx = adr.get_index(self, n);
+				self.modify(idx, Self::asr);
 			}
 			Instruction::BITA(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.and(rhs);
 			}
 			Instruction::BLE =>
 				if (self.get_n() ^ self.get_v()) || self.get_z() {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BLS =>
 				if self.get_c() || self.get_z() {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BLT =>
 				if self.get_n() ^ self.get_v() {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BMI =>
 				if self.get_n() {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BNE =>
 				if !self.get_z() {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BPL =>
-				if self.get_n() {
+				if !self.get_n() {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BRA => {
 				self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
+				return cycles;
 			}
 			Instruction::BSR => {
-				// Ordering?
 				self.SP = self.SP.wrapping_sub(1);
-				self.mem[self.SP as usize] = self.PC;
+				self.write(self.SP, self.PC.wrapping_add(inst.size()));
 				self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
+				return cycles;
 			}
 			Instruction::BVC =>
 				if !self.get_v() {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BVS =>
 				if self.get_v() {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BCC =>
 				if !self.get_c() {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BCS =>
 				if self.get_c() {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BEQ =>
 				if self.get_z() {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BGE =>
 				if !(self.get_n() ^ self.get_v()) {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BGT =>
 				if !((self.get_n() ^ self.get_v()) || self.get_z()) {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::BHI =>
 				if !(self.get_c() || self.get_z()) {
 					self.PC = self.PC.wrapping_add(n).wrapping_add(inst.size());
-					return;
+					return cycles;
 				},
 			Instruction::CLRA => {
 				self.clr();
 				self.A = 0;
 			}
 			Instruction::CLR(adr) => {
-				let idx = adr.get_index(&self, n);
+				let idx = adr.get_index(self, n);
 				self.clr();
-				self.mem[idx] = 0;
+				self.write(idx, 0);
 			}
 			Instruction::CMPA(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.cmp(self.A, rhs);
 			}
 			Instruction::CMPX(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.cmp(self.X, rhs);
 			}
 			Instruction::CMPY(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.cmp(self.Y, rhs);
 			}
 			Instruction::CMPSP(adr) => {
 				let rhs = match adr {
 					CmpspAddr::Data => n,
-					CmpspAddr::Addr => self.mem[n as usize],
+					CmpspAddr::Addr => self.read(n),
 				};
 				self.cmp(self.SP, rhs);
 			}
@@ -361,18 +663,18 @@ impl Flisp {
 				self.A = self.com(self.A);
 			}
 			Instruction::COM(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.mem[idx] = self.com(self.mem[idx]);
+				let idx = adr.get_index(self, n);
+				self.modify(idx, Self::com);
 			}
 			Instruction::DECA => {
 				self.A = self.dec(self.A);
 			}
 			Instruction::DEC(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.mem[idx] = self.dec(self.mem[idx]);
+				let idx = adr.get_index(self, n);
+				self.modify(idx, Self::dec);
 			}
 			Instruction::EORA(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.eora(rhs);
 			}
 			Instruction::EXG(adr) => match adr {
@@ -385,65 +687,65 @@ impl Flisp {
 				self.A = self.inc(self.A);
 			}
 			Instruction::INC(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.mem[idx] = self.inc(self.mem[idx]);
+				let idx = adr.get_index(self, n);
+				self.modify(idx, Self::inc);
 			}
 			Instruction::JMP(adr) => {
-				let target = adr.get_target(&self, n);
+				let target = adr.get_target(self, n);
 				self.PC = target;
-				return;
+				return cycles;
 			}
 			Instruction::JSR(adr) => {
-				let target = adr.get_target(&self, n);
+				let target = adr.get_target(self, n);
 				self.SP = self.SP.wrapping_sub(1);
-				self.mem[self.SP as usize] = self.PC.wrapping_add(inst.size());
+				self.write(self.SP, self.PC.wrapping_add(inst.size()));
 				self.PC = target;
-				return;
+				return cycles;
 			}
 			Instruction::LDA(adr) => {
 				let data = match adr {
 					LdaAddr::Data => n,
-					LdaAddr::Addr => self.mem[n as usize],
-					LdaAddr::nSP => self.mem[(n.wrapping_add(self.SP)) as usize],
-					LdaAddr::nX => self.mem[(n.wrapping_add(self.X)) as usize],
-					LdaAddr::AX => self.mem[(self.A.wrapping_add(self.X)) as usize],
+					LdaAddr::Addr => self.read(n),
+					LdaAddr::nSP => self.read(n.wrapping_add(self.SP)),
+					LdaAddr::nX => self.read(n.wrapping_add(self.X)),
+					LdaAddr::AX => self.read(self.A.wrapping_add(self.X)),
 					LdaAddr::Xplus => {
 						let x = self.X;
 						self.X = self.X.wrapping_add(1);
-						self.mem[x as usize]
+						self.read(x)
 					}
 					LdaAddr::Xminus => {
 						let x = self.X;
 						self.X = self.X.wrapping_sub(1);
-						self.mem[x as usize]
+						self.read(x)
 					}
 					LdaAddr::plusX => {
 						self.X = self.X.wrapping_add(1);
-						self.mem[self.X as usize]
+						self.read(self.X)
 					}
 					LdaAddr::minusX => {
 						self.X = self.X.wrapping_sub(1);
-						self.mem[self.X as usize]
+						self.read(self.X)
 					}
-					LdaAddr::nY => self.mem[(n.wrapping_add(self.Y)) as usize],
-					LdaAddr::AY => self.mem[(self.A.wrapping_add(self.Y)) as usize],
+					LdaAddr::nY => self.read(n.wrapping_add(self.Y)),
+					LdaAddr::AY => self.read(self.A.wrapping_add(self.Y)),
 					LdaAddr::Yplus => {
 						let y = self.Y;
 						self.Y = self.Y.wrapping_add(1);
-						self.mem[y as usize]
+						self.read(y)
 					}
 					LdaAddr::Yminus => {
 						let y = self.Y;
 						self.Y = self.Y.wrapping_sub(1);
-						self.mem[y as usize]
+						self.read(y)
 					}
 					LdaAddr::plusY => {
 						self.Y = self.Y.wrapping_add(1);
-						self.mem[self.Y as usize]
+						self.read(self.Y)
 					}
 					LdaAddr::minusY => {
 						self.Y = self.Y.wrapping_sub(1);
-						self.mem[self.Y as usize]
+						self.read(self.Y)
 					}
 				};
 				self.set_n_from(data);
@@ -459,14 +761,14 @@ impl Flisp {
 				self.X = data;
 			}
 			Instruction::LDY(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.set_n_from(rhs);
 				self.set_z_from(rhs);
 				self.set_v(false);
 				self.Y = rhs;
 			}
 			Instruction::LDSP(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.set_n_from(rhs);
 				self.set_z_from(rhs);
 				self.set_v(false);
@@ -498,91 +800,91 @@ impl Flisp {
 				self.A = self.lsr(self.A);
 			}
 			Instruction::LSR(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.mem[idx] = self.lsr(self.mem[idx]);
+				let idx = adr.get_index(self, n);
+				self.modify(idx, Self::lsr);
 			}
 			Instruction::NEGA => {
 				self.A = self.neg(self.A);
 			}
 			Instruction::NEG(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.mem[idx] = self.neg(self.mem[idx]);
+				let idx = adr.get_index(self, n);
+				self.modify(idx, Self::neg);
 			}
 			Instruction::NOP => {}
 			Instruction::ORA(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.A = self.or(rhs);
 			}
 			Instruction::ORCC => {
 				let rhs = n;
-				self.CC &= rhs;
+				self.CC |= rhs;
 			}
 			Instruction::PSHA => {
 				self.SP = self.SP.wrapping_sub(1);
-				self.mem[self.SP as usize] = self.A;
+				self.write(self.SP, self.A);
 			}
 			Instruction::PSHX => {
 				self.SP = self.SP.wrapping_sub(1);
-				self.mem[self.SP as usize] = self.X;
+				self.write(self.SP, self.X);
 			}
 			Instruction::PSHY => {
 				self.SP = self.SP.wrapping_sub(1);
-				self.mem[self.SP as usize] = self.Y;
+				self.write(self.SP, self.Y);
 			}
 			Instruction::PSHCC => {
 				self.SP = self.SP.wrapping_sub(1);
-				self.mem[self.SP as usize] = self.CC;
+				self.write(self.SP, self.CC);
 			}
 			Instruction::PULA => {
-				self.A = self.mem[self.SP as usize];
+				self.A = self.read(self.SP);
 				self.SP = self.SP.wrapping_add(1);
 			}
 			Instruction::PULX => {
-				self.X = self.mem[self.SP as usize];
+				self.X = self.read(self.SP);
 				self.SP = self.SP.wrapping_add(1);
 			}
 			Instruction::PULY => {
-				self.Y = self.mem[self.SP as usize];
+				self.Y = self.read(self.SP);
 				self.SP = self.SP.wrapping_add(1);
 			}
 			Instruction::PULCC => {
-				self.CC = self.mem[self.SP as usize];
+				self.CC = self.read(self.SP);
 				self.SP = self.SP.wrapping_add(1);
 			}
 			Instruction::ROLA => {
 				self.A = self.rol(self.A);
 			}
 			Instruction::ROL(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.mem[idx] = self.rol(self.mem[idx]);
+				let idx = adr.get_index(self, n);
+				self.modify(idx, Self::rol);
 			}
 			Instruction::RORA => {
 				self.A = self.ror(self.A);
 			}
 			Instruction::ROR(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.mem[idx] = self.ror(self.mem[idx]);
+				let idx = adr.get_index(self, n);
+				self.modify(idx, Self::ror);
 			}
 			Instruction::RTS => {
-				self.PC = self.mem[self.SP as usize];
+				self.PC = self.read(self.SP);
 				self.SP = self.SP.wrapping_add(1);
-				return;
+				return cycles;
 			}
 			Instruction::RTI => {
-				self.CC = self.mem[self.SP as usize];
+				self.CC = self.read(self.SP);
 				self.SP = self.SP.wrapping_add(1);
-				self.A = self.mem[self.SP as usize];
+				self.A = self.read(self.SP);
 				self.SP = self.SP.wrapping_add(1);
-				self.X = self.mem[self.SP as usize];
+				self.X = self.read(self.SP);
 				self.SP = self.SP.wrapping_add(1);
-				self.Y = self.mem[self.SP as usize];
+				self.Y = self.read(self.SP);
 				self.SP = self.SP.wrapping_add(1);
-				self.PC = self.mem[self.SP as usize];
+				self.PC = self.read(self.SP);
 				self.SP = self.SP.wrapping_add(1);
-				return;
+				return cycles;
 			}
 			Instruction::SBCA(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.A = self.sub(rhs.wrapping_add(self.get_c() as u8));
 			}
 			Instruction::STA(adr) => {
@@ -629,24 +931,24 @@ impl Flisp {
 						self.Y = self.Y.wrapping_sub(1);
 						self.Y
 					}
-				} as usize;
-				self.mem[idx] = self.A;
+				};
+				self.write(idx, self.A);
 			}
 			Instruction::STX(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.mem[idx as usize] = self.X;
+				let idx = adr.get_index(self, n);
+				self.write(idx, self.X);
 			}
 			Instruction::STY(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.mem[idx as usize] = self.Y;
+				let idx = adr.get_index(self, n);
+				self.write(idx, self.Y);
 			}
 			Instruction::STSP(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.mem[idx as usize] = self.SP;
+				let idx = adr.get_index(self, n);
+				self.write(idx, self.SP);
 			}
 
 			Instruction::SUBA(adr) => {
-				let rhs = adr.get_value(&self, n);
+				let rhs = adr.get_value(self, n);
 				self.A = self.sub(rhs);
 			}
 
@@ -664,428 +966,30 @@ impl Flisp {
 				self.tst(self.A);
 			}
 			Instruction::TST(adr) => {
-				let idx = adr.get_index(&self, n);
-				self.tst(self.mem[idx])
+				let idx = adr.get_index(self, n);
+				let data = self.read(idx);
+				self.tst(data)
 			}
 		}
-		if self.PC == 0xFF {
-			self.PC = self.mem[0xFF]
+		self.PC = self.PC.wrapping_add(inst.size());
+		if inst.is_branch() {
+			// Not taken, the offset is never added to PC
+			cycles - 1
 		} else {
-			self.PC = self.PC.wrapping_add(inst.size());
+			cycles
 		}
 	}
 
 	//Prints line of disassembly to the out string and returns the index of the next instruction,
 	// taking instruction parametres into account
 	pub fn print_disassembly<T: Write>(&self, out: &mut T, idx: u8) -> Result<u8> {
-		let index = idx as usize;
-		let read: Result<Instruction> = self.mem[index].try_into();
-		let next = self.mem[idx.wrapping_add(1) as usize];
-		match read {
-			Ok(inst) => match inst {
-				Instruction::ADCA(adr) => {
-					write!(out, "ADCA    ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::ADDA(adr) => {
-					write!(out, "ADDA    ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::ANDA(adr) => {
-					write!(out, "ANDA    ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::ANDCC => {
-					write!(out, "ANDCC   ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::ASLA => {
-					write!(out, "ASLA    ")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::ASL(adr) => {
-					write!(out, "ASL     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::ASRA => {
-					write!(out, "ASRA    ")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::ASR(adr) => {
-					write!(out, "ASR     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::BITA(adr) => {
-					write!(out, "BITA    ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BLE => {
-					write!(out, "BLE     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BLS => {
-					write!(out, "BLS     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BLT => {
-					write!(out, "BLT     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BMI => {
-					write!(out, "BMI     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BNE => {
-					write!(out, "BNE     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BPL => {
-					write!(out, "BPL     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BRA => {
-					write!(out, "BRA     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BSR => {
-					write!(out, "BSR     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BVC => {
-					write!(out, "BVC     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BVS => {
-					write!(out, "BVS     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BCC => {
-					write!(out, "BCC     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BCS => {
-					write!(out, "BCS     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BEQ => {
-					write!(out, "BEQ     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BGE => {
-					write!(out, "BGE     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BGT => {
-					write!(out, "BGT     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::BHI => {
-					write!(out, "BHI     ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::CLRA => {
-					write!(out, "CLRA    ${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::CLR(adr) => {
-					write!(out, "CLR     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::CMPA(adr) => {
-					write!(out, "CMPA    ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::CMPX(adr) => {
-					write!(out, "CMPA    ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::CMPY(adr) => {
-					write!(out, "CMPY    ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::CMPSP(adr) => {
-					match adr {
-						CmpspAddr::Data => write!(out, "CMPSP   #${:02X}", next)?,
-						CmpspAddr::Addr => write!(out, "CMPSP   ${:02X}", next)?,
-					}
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::COMA => {
-					write!(out, "COMA")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::COM(adr) => {
-					write!(out, "COM     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::DECA => {
-					write!(out, "DECA")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::DEC(adr) => {
-					write!(out, "DEC     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::EORA(adr) => {
-					write!(out, "EORA    ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::EXG(adr) => {
-					match adr {
-						ExgAddr::XY => write!(out, "EXG     X,Y")?,
-						ExgAddr::ACC => write!(out, "EXG     A,CC")?,
-						ExgAddr::XSP => write!(out, "EXG     X,SP")?,
-						ExgAddr::YSP => write!(out, "EXG     Y,SP")?,
-					}
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::INCA => {
-					write!(out, "INCA")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::INC(adr) => {
-					write!(out, "INC     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::JMP(adr) => {
-					write!(out, "JMP     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::JSR(adr) => {
-					write!(out, "JSR     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::LDA(adr) => {
-					let (_, ret) = match adr {
-						LdaAddr::Data => (write!(out, "LDA     #${:02X}", next)?, 2),
-						LdaAddr::Addr => (write!(out, "LDA     ${:02X}", next)?, 2),
-						LdaAddr::nSP => (write!(out, "LDA     ${:02X},SP", next)?, 2),
-						LdaAddr::nX => (write!(out, "LDA     ${:02X},X", next)?, 2),
-						LdaAddr::AX => (write!(out, "LDA     A,Y")?, 1),
-						LdaAddr::Xplus => (write!(out, "LDA     X+")?, 1),
-						LdaAddr::Xminus => (write!(out, "LDA     X-")?, 1),
-						LdaAddr::plusX => (write!(out, "LDA     +X")?, 1),
-						LdaAddr::minusX => (write!(out, "LDA     -X")?, 1),
-						LdaAddr::nY => (write!(out, "LDA     ${:02X},Y", next)?, 2),
-						LdaAddr::AY => (write!(out, "LDA     A,Y")?, 1),
-						LdaAddr::Yplus => (write!(out, "LDA     Y+")?, 1),
-						LdaAddr::Yminus => (write!(out, "LDA     Y-")?, 1),
-						LdaAddr::plusY => (write!(out, "LDA     +Y")?, 1),
-						LdaAddr::minusY => (write!(out, "LDA     -Y")?, 1),
-					};
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::LDX(adr) => {
-					write!(out, "LDX     ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::LDY(adr) => {
-					write!(out, "LDY     ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::LDSP(adr) => {
-					write!(out, "LDSP    ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::LEAX(adr) => {
-					match adr {
-						LeaxAddr::nX => write!(out, "LEAX    ${:02X},X", next)?,
-						LeaxAddr::nSP => write!(out, "LEAX    ${:02X},SP", next)?,
-					}
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::LEAY(adr) => {
-					match adr {
-						LeayAddr::nY => write!(out, "LEAY    ${:02X},Y", next)?,
-						LeayAddr::nSP => write!(out, "LEAY    ${:02X},SP", next)?,
-					}
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::LEASP(adr) => {
-					match adr {
-						LeaspAddr::nX => write!(out, "LEASP    ${:02X},X", next)?,
-						LeaspAddr::nY => write!(out, "LEASP    ${:02X},Y", next)?,
-						LeaspAddr::nSP => write!(out, "LEASP    ${:02X},SP", next)?,
-					}
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::LSRA => {
-					write!(out, "LSRA")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::LSR(adr) => {
-					write!(out, "LSR     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::NEGA => {
-					write!(out, "NEGA")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::NEG(adr) => {
-					write!(out, "NEG     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::NOP => {
-					write!(out, "NOP")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::ORA(adr) => {
-					write!(out, "ORA     ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::ORCC => {
-					write!(out, "ORCC    #${:02X}", next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::PSHA => {
-					write!(out, "PSHA")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::PSHX => {
-					write!(out, "PSHX")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::PSHY => {
-					write!(out, "PSHY")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::PSHCC => {
-					write!(out, "PSHCC")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::PULA => {
-					write!(out, "PULA")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::PULX => {
-					write!(out, "PULX")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::PULY => {
-					write!(out, "PULY")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::PULCC => {
-					write!(out, "PULCC")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::ROLA => {
-					write!(out, "ROLA")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::ROL(adr) => {
-					write!(out, "ROL     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::RORA => {
-					write!(out, "RORA")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::ROR(adr) => {
-					write!(out, "ROR     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::RTS => {
-					write!(out, "RTS")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::RTI => {
-					write!(out, "RTI")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::SBCA(adr) => {
-					write!(out, "SBCA    ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::STA(adr) => {
-					let (_, ret) = match adr {
-						StaAddr::Addr => (write!(out, "STA     ${:02X}", next)?, 2),
-						StaAddr::nSP => (write!(out, "STA     ${:02X},SP", next)?, 2),
-						StaAddr::nX => (write!(out, "STA     ${:02X},X", next)?, 2),
-						StaAddr::AX => (write!(out, "STA     A,Y")?, 1),
-						StaAddr::Xplus => (write!(out, "STA     X+")?, 1),
-						StaAddr::Xminus => (write!(out, "STA     X-")?, 1),
-						StaAddr::plusX => (write!(out, "STA     +X")?, 1),
-						StaAddr::minusX => (write!(out, "STA     -X")?, 1),
-						StaAddr::nY => (write!(out, "STA     ${:02X},Y", next)?, 2),
-						StaAddr::AY => (write!(out, "STA     A,Y")?, 1),
-						StaAddr::Yplus => (write!(out, "STA     Y+")?, 1),
-						StaAddr::Yminus => (write!(out, "STA     Y-")?, 1),
-						StaAddr::plusY => (write!(out, "STA     +Y")?, 1),
-						StaAddr::minusY => (write!(out, "STA     -Y")?, 1),
-					};
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::STX(adr) => {
-					write!(out, "STX     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::STY(adr) => {
-					write!(out, "STY     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::STSP(adr) => {
-					write!(out, "STSP     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-				Instruction::SUBA(adr) => {
-					write!(out, "SUBA    ")?;
-					adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(2))
-				}
-				Instruction::TFR(adr) => {
-					match adr {
-						TfrAddr::ACC => write!(out, "TFR     A,CC")?,
-						TfrAddr::CCA => write!(out, "TFR     CC,A")?,
-						TfrAddr::XY => write!(out, "TFR     X,Y")?,
-						TfrAddr::YX => write!(out, "TFR     Y,X")?,
-						TfrAddr::XSP => write!(out, "TFR     X,SP")?,
-						TfrAddr::SPX => write!(out, "TFR     SP,X")?,
-						TfrAddr::YSP => write!(out, "TFR     Y,SP")?,
-						TfrAddr::SPY => write!(out, "TFR     SP,Y")?,
-					}
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::TSTA => {
-					write!(out, "TSTA")?;
-					Ok(idx.wrapping_add(1))
-				}
-				Instruction::TST(adr) => {
-					write!(out, "TST     ")?;
-					let ret = adr.write_with_next(out, next)?;
-					Ok(idx.wrapping_add(ret))
-				}
-			},
+		match decode(&self.mem, idx) {
+			Ok(decoded) => {
+				write!(out, "{}", decoded)?;
+				Ok(idx.wrapping_add(decoded.size))
+			}
 			Err(_) => {
-				write!(out, "FCB     ${:02X}", self.mem[index])?;
+				write!(out, "FCB     ${:02X}", self.mem.peek(idx))?;
 				Ok(idx.wrapping_add(1))
 			}
 		}
@@ -1096,15 +1000,7 @@ impl FromStr for Flisp {
 	type Err = FlispError;
 
 	fn from_str(s: &str) -> result::Result<Self, Self::Err> {
-		let mut flisp = Flisp {
-			A: 0,
-			Y: 0,
-			X: 0,
-			CC: 0,
-			SP: 0,
-			PC: 0xFF,
-			mem: [0; 256],
-		};
+		let mut flisp = Flisp::default();
 
 		for line in s.lines().filter(|l| !l.is_empty()) {
 			let line = line
@@ -1125,35 +1021,28 @@ impl FromStr for Flisp {
 			flisp.mem[adr as usize] = val;
 		}
 
-		flisp.PC = flisp.mem[255];
+		flisp.reset();
 
 		Ok(flisp)
 	}
 }
 
 impl Default for Flisp {
+	/// The state right after [`power_on`](Flisp::power_on)
 	fn default() -> Self {
-		Flisp {
-			A: 0,
-			Y: 0,
-			X: 0,
-			CC: 0,
-			SP: 0,
-			PC: 0xFF,
-			mem: [0; 256],
-		}
+		Flisp::new([0; 256])
 	}
 }
 
 #[cfg(test)]
 mod test {
-	use std::{collections::VecDeque, str::FromStr};
+	use std::str::FromStr;
 
 	use crate::*;
 	#[test]
 	fn primes() {
 		let mut step = 0;
-		let mut step_history = VecDeque::with_capacity(128);
+		let mut detector = LoopDetector::new(128);
 		let primes_source = include_str!("deps/primes_source.fmem");
 		let mut flisp = Flisp::from_str(primes_source).unwrap();
 		let starting_mem = [
@@ -1201,13 +1090,9 @@ mod test {
 		assert_eq!(flisp.mem, starting_mem);
 
 		while flisp.PC != 0x9F {
-			if step_history.contains(&flisp) {
-				panic!("Infinite loop\nStep: {}\nState: {:X?}", step, &flisp);
+			if let Some(reason) = detector.check(&flisp) {
+				panic!("{}\nStep: {}\nState: {:X?}", reason, step, &flisp);
 			}
-			while step_history.len() >= 128 {
-				step_history.pop_front();
-			}
-			step_history.push_back(flisp.clone());
 			flisp.step();
 			step += 1;
 			assert!(
@@ -1229,4 +1114,200 @@ mod test {
 
 		assert_eq!(flisp.mem, ending_mem);
 	}
+
+	#[test]
+	fn cycle_count() {
+		let mut flisp = Flisp::default();
+		// LDA #3, DECA, BNE -3, BRA -2
+		let program = [0xF0, 0x03, 0x08, 0x25, 0xFD, 0x21, 0xFE];
+		flisp.mem[0x10..0x10 + program.len()].copy_from_slice(&program);
+		flisp.PC = 0x10;
+
+		assert_eq!(flisp.step(), 2);
+		while flisp.PC != 0x15 {
+			flisp.step();
+		}
+		// 3 DECA, 2 taken and 1 not taken BNE
+		assert_eq!(flisp.cycles, 2 + 3 * 3 + 2 * 4 + 3);
+
+		assert_eq!(flisp.run_for_cycles(10), 12);
+		assert_eq!(flisp.PC, 0x15);
+		assert_eq!(flisp.cycles, 22 + 12);
+
+		// BNE -3 both ways
+		flisp.PC = 0x13;
+		flisp.CC = 0;
+		assert_eq!(flisp.step(), 4);
+		assert_eq!(flisp.PC, 0x12);
+		flisp.PC = 0x13;
+		flisp.CC = 0b0100;
+		assert_eq!(flisp.step(), 3);
+		assert_eq!(flisp.PC, 0x15);
+	}
+
+	#[test]
+	fn interrupt_round_trip() {
+		let mut flisp = Flisp::default();
+		// Main: BRA -2, handler: INC $50, RTI
+		flisp.mem[0x20..0x22].copy_from_slice(&[0x21, 0xFE]);
+		flisp.mem[0x30..0x33].copy_from_slice(&[0x37, 0x50, 0x44]);
+		flisp.mem[0xFD] = 0x30;
+		flisp.PC = 0x20;
+		flisp.SP = 0x80;
+		flisp.CC = 0b0_0101;
+		flisp.A = 0x11;
+		flisp.X = 0x22;
+		flisp.Y = 0x33;
+
+		flisp.raise_irq();
+		flisp.step();
+		assert_eq!(flisp.PC, 0x30);
+		assert_eq!(flisp.SP, 0x7B);
+		assert_eq!(flisp.CC, 0b1_0101);
+		// Pushed as PC, Y, X, A, CC, leaving CC on top of the stack
+		assert_eq!(flisp.mem[0x7F], 0x20);
+		assert_eq!(flisp.mem[0x7E], 0x33);
+		assert_eq!(flisp.mem[0x7D], 0x22);
+		assert_eq!(flisp.mem[0x7C], 0x11);
+		assert_eq!(flisp.mem[0x7B], 0b0_0101);
+
+		// Masked while the handler runs
+		flisp.raise_irq();
+		flisp.step();
+		flisp.step();
+		assert_eq!(flisp.mem[0x50], 1);
+		assert_eq!(flisp.PC, 0x20);
+		assert_eq!(flisp.SP, 0x80);
+		assert_eq!(flisp.CC, 0b0_0101);
+		assert_eq!((flisp.A, flisp.X, flisp.Y), (0x11, 0x22, 0x33));
+
+		// Serviced again once RTI restores CC
+		flisp.step();
+		assert_eq!(flisp.PC, 0x30);
+		assert!(!flisp.irq);
+
+		flisp.CC = 0b1_0000;
+		flisp.PC = 0x20;
+		flisp.raise_irq();
+		flisp.step();
+		assert_eq!(flisp.PC, 0x20);
+		assert!(flisp.irq);
+	}
+
+	#[test]
+	fn reset() {
+		let mut flisp = Flisp::default();
+		flisp.mem[0xFF] = 0x40;
+		flisp.mem[0x40] = 0x07;
+		flisp.A = 1;
+		flisp.SP = 0x80;
+		flisp.CC = 0b0_1111;
+		flisp.PC = 0x12;
+		flisp.raise_irq();
+		flisp.step();
+
+		flisp.reset();
+		assert_eq!(flisp.PC, 0x40);
+		assert_eq!(flisp.CC, 0b1_0000);
+		assert_eq!((flisp.A, flisp.X, flisp.Y, flisp.SP), (0, 0, 0, 0));
+		assert_eq!(flisp.cycles, 0);
+		assert!(!flisp.irq);
+		assert_eq!(flisp.mem[0x40], 0x07);
+
+		flisp.power_on();
+		assert_eq!(flisp, Flisp::default());
+	}
+
+	#[test]
+	fn illegal_opcodes() {
+		let mut flisp = Flisp::default();
+		// NOP, illegal $03
+		flisp.mem[0x20..0x22].copy_from_slice(&[0x00, 0x03]);
+		flisp.mem[0xFD] = 0x40;
+		flisp.PC = 0x20;
+		let start = flisp.clone();
+
+		flisp.step();
+		assert_eq!(flisp.illegal_opcode, None);
+		assert_eq!(flisp.step(), 2);
+		assert_eq!(flisp.PC, 0x40);
+		let illegal = IllegalOpcode {
+			addr: 0x21,
+			byte: 0x03,
+		};
+		assert_eq!(flisp.illegal_opcode, Some(illegal));
+		assert_eq!(illegal.to_string(), "Illegal opcode $03 at $21");
+
+		let mut flisp = start.clone();
+		flisp.on_illegal_opcode = IllegalOpcodePolicy::Halt;
+		assert_eq!(
+			flisp.run(
+				&Breakpoints::new(),
+				&mut LoopDetector::new(0),
+				Limits::default()
+			),
+			(
+				StopReason::IllegalOpcode {
+					addr: 0x21,
+					byte: 0x03
+				},
+				2
+			)
+		);
+		assert_eq!(flisp.PC, 0x21);
+		assert_eq!(flisp.step(), 0);
+		assert_eq!(flisp.PC, 0x21);
+
+		// The other ways of running stop there too instead of spinning
+		let mut flisp = start.clone();
+		flisp.on_illegal_opcode = IllegalOpcodePolicy::Halt;
+		assert_eq!(flisp.run_for_cycles(1000), 2);
+		assert_eq!(flisp.PC, 0x21);
+		let mut flisp = start.clone();
+		flisp.on_illegal_opcode = IllegalOpcodePolicy::Halt;
+		assert_eq!(flisp.run_until_break(&Breakpoints::new(), 1000), None);
+		assert_eq!(flisp.PC, 0x21);
+
+		let mut flisp = start;
+		flisp.on_illegal_opcode = IllegalOpcodePolicy::Panic;
+		flisp.step();
+		let res = std::panic::catch_unwind(move || flisp.step());
+		assert!(res.is_err());
+	}
+
+	#[test]
+	fn branches_and_orcc() {
+		let mut flisp = Flisp::default();
+		// BRA +2
+		flisp.mem[0x10..0x12].copy_from_slice(&[0x21, 0x02]);
+		flisp.PC = 0x10;
+		flisp.step();
+		assert_eq!(flisp.PC, 0x14);
+
+		// BSR +2 pushes the address after itself
+		flisp.mem[0x10..0x12].copy_from_slice(&[0x20, 0x02]);
+		flisp.PC = 0x10;
+		flisp.SP = 0x80;
+		flisp.step();
+		assert_eq!(flisp.PC, 0x14);
+		assert_eq!(flisp.SP, 0x7F);
+		assert_eq!(flisp.mem[0x7F], 0x12);
+
+		// BPL +2 is taken when N is clear
+		flisp.mem[0x10..0x12].copy_from_slice(&[0x23, 0x02]);
+		flisp.PC = 0x10;
+		flisp.step();
+		assert_eq!(flisp.PC, 0x14);
+		flisp.PC = 0x10;
+		flisp.CC = 0b1000;
+		flisp.step();
+		assert_eq!(flisp.PC, 0x12);
+
+		// ORCC #$10 sets the I flag and keeps the others
+		flisp.mem[0x10..0x12].copy_from_slice(&[0x02, 0x10]);
+		flisp.PC = 0x10;
+		flisp.CC = 0b0_0101;
+		flisp.step();
+		assert_eq!(flisp.CC, 0b1_0101);
+	}
 }
@@ -0,0 +1,183 @@
+use std::{collections::VecDeque, fmt};
+
+use crate::{trace::Registers, *};
+
+/// Why [`Flisp::run`] returned
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StopReason {
+	/// PC reached a breakpoint whose condition holds
+	Breakpoint(u8),
+	/// PC reached a `BRA *` or `JMP *` with interrupts masked, the usual way to end a program
+	Halted(u8),
+	/// The machine got back to a state it was in `period` steps earlier, devices included, so
+	/// it will keep going around forever
+	InfiniteLoop {
+		pc: u8,
+		period: usize,
+	},
+	/// Fetched `byte`, which isn't an instruction, at `addr` while the policy is
+	/// [`IllegalOpcodePolicy::Halt`]
+	IllegalOpcode {
+		addr: u8,
+		byte: u8,
+	},
+	StepLimit,
+	CycleLimit,
+}
+
+impl fmt::Display for StopReason {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			StopReason::Breakpoint(adr) => write!(f, "Breakpoint at ${:02X}", adr),
+			StopReason::Halted(adr) => write!(f, "Halted at ${:02X}", adr),
+			StopReason::InfiniteLoop { pc, period } => write!(
+				f,
+				"Infinite loop at ${:02X}, repeats every {} steps",
+				pc, period
+			),
+			StopReason::IllegalOpcode { addr, byte } =>
+				write!(f, "Illegal opcode ${:02X} at ${:02X}", byte, addr),
+			StopReason::StepLimit => write!(f, "Step limit reached"),
+			StopReason::CycleLimit => write!(f, "Cycle limit reached"),
+		}
+	}
+}
+
+/// How long [`Flisp::run`] may go on. `None` means no limit
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Limits {
+	pub max_steps: Option<u64>,
+	pub max_cycles: Option<u64>,
+}
+
+/// Everything that decides what the machine does next. The cycle counter never repeats and
+/// isn't part of it
+#[derive(Debug, Clone, PartialEq)]
+struct State<B> {
+	registers: Registers,
+	irq: bool,
+	mem: B,
+}
+
+/// Remembers the states of the last `window` steps to notice when one comes back. Loops longer
+/// than the window go unnoticed
+#[derive(Debug, Clone)]
+pub struct LoopDetector<B: Bus = [u8; 256]> {
+	states: VecDeque<State<B>>,
+	window: usize,
+}
+
+impl<B: Bus + Clone + PartialEq> LoopDetector<B> {
+	pub fn new(window: usize) -> Self {
+		LoopDetector {
+			states: VecDeque::with_capacity(window),
+			window,
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.states.clear();
+	}
+
+	/// Looks at the state `flisp` is in before its next step. Returns why it should stop, if it
+	/// is halted or has been in the same state before
+	pub fn check(&mut self, flisp: &Flisp<B>) -> Option<StopReason> {
+		if flisp.halted() {
+			return Some(StopReason::Halted(flisp.PC));
+		}
+		if self.window == 0 {
+			return None;
+		}
+		let state = State {
+			registers: Registers::of(flisp),
+			irq: flisp.irq,
+			mem: flisp.mem.clone(),
+		};
+		if let Some(idx) = self.states.iter().rposition(|old| *old == state) {
+			return Some(StopReason::InfiniteLoop {
+				pc: flisp.PC,
+				period: self.states.len() - idx,
+			});
+		}
+		while self.states.len() >= self.window {
+			self.states.pop_front();
+		}
+		self.states.push_back(state);
+		None
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn run(source: &str, limits: Limits) -> StopReason {
+		let mut flisp =
+			assemble(&format!("\tORG\t$20\n{}\tORG\t$FF\n\tFCB\t$20\n", source)).unwrap();
+		flisp
+			.run(&Breakpoints::new(), &mut LoopDetector::new(128), limits)
+			.0
+	}
+
+	#[test]
+	fn stop_reasons() {
+		let limits = Limits {
+			max_steps: Some(1000),
+			max_cycles: None,
+		};
+		assert_eq!(
+			run("\tLDA\t#1\n\tBRA\t*\n", limits),
+			StopReason::Halted(0x22)
+		);
+		assert_eq!(run("\tJMP\t*\n", limits), StopReason::Halted(0x20));
+
+		// With interrupts enabled an interrupt could still get it out, but nothing changes
+		assert_eq!(
+			run("\tANDCC\t#$EF\n\tBRA\t*\n", limits),
+			StopReason::InfiniteLoop {
+				pc: 0x22,
+				period: 1
+			}
+		);
+		assert_eq!(
+			run("loop:\tLDA\t#1\n\tLDA\t#2\n\tBRA\tloop\n", limits),
+			StopReason::InfiniteLoop {
+				pc: 0x22,
+				period: 3
+			}
+		);
+		// Takes 512 steps to come around, more than the window holds
+		assert_eq!(
+			run("loop:\tINCA\n\tBRA\tloop\n", limits),
+			StopReason::StepLimit
+		);
+		assert_eq!(
+			run(
+				"loop:\tINCA\n\tBRA\tloop\n",
+				Limits {
+					max_steps: None,
+					max_cycles: Some(100)
+				}
+			),
+			StopReason::CycleLimit
+		);
+	}
+
+	#[test]
+	fn devices_are_part_of_the_state() {
+		// The count only lives in the hex display, RAM and registers go around every two steps
+		let flisp =
+			assemble("\tORG\t$20\nloop:\tINC\t$FC\n\tBRA\tloop\n\tORG\t$FF\n\tFCB\t$20\n").unwrap();
+		let mut board = Board::new(flisp.mem);
+		board.fc = IoDevice::HexDisplay(0);
+		let mut flisp = Flisp::new(board);
+		let limits = Limits {
+			max_steps: Some(100),
+			max_cycles: None,
+		};
+		assert_eq!(
+			flisp.run(&Breakpoints::new(), &mut LoopDetector::new(16), limits),
+			(StopReason::StepLimit, 100)
+		);
+	}
+}
@@ -0,0 +1,279 @@
+use std::fmt::{self, Write};
+
+use crate::{
+	srecord::hex_bytes,
+	stepper::{Direction, Stepper},
+	timer::Timer,
+	trace::Registers,
+	*,
+};
+
+/// Written in the header. Bump it when the format changes, older versions must still load
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+const HEADER: &str = "flisp snapshot";
+
+fn parse_hex(s: &str) -> Option<u8> {
+	hex_bytes(s)
+		.filter(|bytes| bytes.len() == 1)
+		.map(|bytes| bytes[0])
+}
+
+fn parse_registers(s: &str) -> Option<Registers> {
+	let mut regs = Registers::of(&Flisp::default());
+	for field in s.split_whitespace() {
+		let (name, value) = field.split_once('=')?;
+		let value = parse_hex(value)?;
+		let reg = match name {
+			"A" => &mut regs.A,
+			"X" => &mut regs.X,
+			"Y" => &mut regs.Y,
+			"SP" => &mut regs.SP,
+			"PC" => &mut regs.PC,
+			"CC" => &mut regs.CC,
+			_ => return None,
+		};
+		*reg = value;
+	}
+	Some(regs)
+}
+
+/// The next word, which must be `name=value`
+fn field<'a>(words: &mut impl Iterator<Item = &'a str>, name: &str) -> Option<&'a str> {
+	words.next()?.strip_prefix(name)?.strip_prefix('=')
+}
+
+/// `bargraph 5A` or `nothing`. A stepper motor is followed by its position, direction and
+/// step counts, a timer by its count
+fn parse_device(s: &str) -> Option<IoDevice> {
+	let mut words = s.split_whitespace();
+	let mut device = IoDevice::from_name(words.next()?)?;
+	if let Some(value) = words.next() {
+		device.set_value(parse_hex(value)?);
+	}
+	match &mut device {
+		IoDevice::StepperMotor(motor) => {
+			motor.position = field(&mut words, "position")?.parse().ok()?;
+			motor.direction = Direction::from_name(field(&mut words, "direction")?)?;
+			motor.missed_steps = field(&mut words, "missed")?.parse().ok()?;
+			motor.invalid_steps = field(&mut words, "invalid")?.parse().ok()?;
+		}
+		IoDevice::Timer(timer) => {
+			timer.remaining = field(&mut words, "remaining")?.parse().ok()?;
+			timer.expired = field(&mut words, "expired")?.parse().ok()?;
+		}
+		_ => {}
+	}
+	match words.next() {
+		Some(_) => None,
+		None => Some(device),
+	}
+}
+
+fn write_device<W: Write>(out: &mut W, port: &str, device: &IoDevice) -> fmt::Result {
+	write!(out, "{} {}", port, device.name())?;
+	if let Some(value) = device.value() {
+		write!(out, " {:02X}", value)?;
+	}
+	if let IoDevice::StepperMotor(Stepper {
+		position,
+		direction,
+		missed_steps,
+		invalid_steps,
+		..
+	}) = device
+	{
+		write!(
+			out,
+			" position={} direction={} missed={} invalid={}",
+			position,
+			direction.name(),
+			missed_steps,
+			invalid_steps
+		)?;
+	}
+	if let IoDevice::Timer(Timer {
+		remaining, expired, ..
+	}) = device
+	{
+		write!(out, " remaining={} expired={}", remaining, expired)?;
+	}
+	writeln!(out)
+}
+
+impl Flisp<Board> {
+	/// Writes the whole machine: registers, cycle count, pending interrupt, the devices on the
+	/// ports and memory. Memory behind an attached device is written too, so it comes back if
+	/// the device is detached
+	pub fn write_snapshot<W: Write>(&self, out: &mut W) -> fmt::Result {
+		writeln!(out, "{} {}", HEADER, SNAPSHOT_VERSION)?;
+		writeln!(out, "registers {}", Registers::of(self))?;
+		writeln!(out, "cycles {}", self.cycles)?;
+		writeln!(out, "irq {}", self.irq)?;
+		write_device(out, "fb", &self.mem.fb)?;
+		write_device(out, "fc", &self.mem.fc)?;
+		for (row, bytes) in self.mem.ram.chunks(16).enumerate() {
+			write!(out, "mem {:02X}:", row * 16)?;
+			for byte in bytes {
+				write!(out, " {:02X}", byte)?;
+			}
+			writeln!(out)?;
+		}
+		Ok(())
+	}
+
+	/// Restores a machine written by [`write_snapshot`](Flisp::write_snapshot). Anything left
+	/// out is as after power on, except the registers which are required
+	pub fn from_snapshot(text: &str) -> Result<Self> {
+		let mut lines = text
+			.lines()
+			.enumerate()
+			.map(|(idx, line)| (idx + 1, line.trim()))
+			.filter(|(_, line)| !line.is_empty());
+		let version = lines
+			.next()
+			.and_then(|(_, line)| line.strip_prefix(HEADER))
+			.and_then(|version| version.trim().parse().ok())
+			.ok_or(FlispError::InvalidSnapshot { line: 1 })?;
+		if version > SNAPSHOT_VERSION {
+			return Err(FlispError::UnsupportedSnapshotVersion(version));
+		}
+
+		let mut flisp = Flisp::new(Board::new([0; 256]));
+		let mut registers = None;
+		for (line_no, line) in lines {
+			let invalid = FlispError::InvalidSnapshot { line: line_no };
+			let (key, rest) = line.split_once(' ').ok_or(invalid)?;
+			let rest = rest.trim();
+			match key {
+				"registers" => registers = Some(parse_registers(rest).ok_or(invalid)?),
+				"cycles" => flisp.cycles = rest.parse().map_err(|_| invalid)?,
+				"irq" => flisp.irq = rest.parse().map_err(|_| invalid)?,
+				"fb" => flisp.mem.fb = parse_device(rest).ok_or(invalid)?,
+				"fc" => flisp.mem.fc = parse_device(rest).ok_or(invalid)?,
+				"mem" => {
+					let (adr, bytes) = rest.split_once(':').ok_or(invalid)?;
+					let adr = parse_hex(adr).ok_or(invalid)? as usize;
+					let bytes = bytes
+						.split_whitespace()
+						.map(parse_hex)
+						.collect::<Option<Vec<_>>>()
+						.ok_or(invalid)?;
+					flisp
+						.mem
+						.ram
+						.get_mut(adr..adr + bytes.len())
+						.ok_or(invalid)?
+						.copy_from_slice(&bytes);
+				}
+				_ => return Err(invalid),
+			}
+		}
+
+		let registers = registers.ok_or(FlispError::InvalidSnapshot {
+			line: text.lines().count(),
+		})?;
+		flisp.A = registers.A;
+		flisp.X = registers.X;
+		flisp.Y = registers.Y;
+		flisp.SP = registers.SP;
+		flisp.PC = registers.PC;
+		flisp.CC = registers.CC;
+		Ok(flisp)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		let flisp = assemble(
+			"\
+	ORG	$20
+	LDA	#3
+	STA	$FC
+	LDA	$FB
+	ORG	$FF
+	FCB	$20
+",
+		)
+		.unwrap();
+		let mut board = Board::new(flisp.mem);
+		board.fb = IoDevice::DILSwitch(0x81);
+		board.fc = IoDevice::Bargraph(0);
+		let mut flisp = Flisp::new(board);
+		flisp.step();
+		flisp.step();
+		flisp.raise_irq();
+
+		let mut text = String::new();
+		flisp.write_snapshot(&mut text).unwrap();
+		let lines = text.lines().collect::<Vec<_>>();
+		assert_eq!(
+			lines[..6],
+			[
+				"flisp snapshot 1",
+				"registers A=03 X=00 Y=00 SP=00 PC=24 CC=10",
+				"cycles 5",
+				"irq true",
+				"fb dilswitch 81",
+				"fc bargraph 03",
+			]
+		);
+		assert_eq!(
+			lines[8],
+			"mem 20: F0 03 E1 FC F1 FB 00 00 00 00 00 00 00 00 00 00"
+		);
+
+		let restored = Flisp::from_snapshot(&text).unwrap();
+		assert_eq!(restored, flisp);
+
+		assert_eq!(
+			Flisp::from_snapshot("flisp snapshot 2\n"),
+			Err(FlispError::UnsupportedSnapshotVersion(2))
+		);
+
+		let mut motor = Stepper::default();
+		motor.write(0b0001);
+		motor.write(0b1001);
+		flisp.mem.fc = IoDevice::StepperMotor(motor);
+		let mut text = String::new();
+		flisp.write_snapshot(&mut text).unwrap();
+		assert_eq!(
+			text.lines().nth(5),
+			Some("fc steppermotor 09 position=-1 direction=ccw missed=0 invalid=0")
+		);
+		assert_eq!(Flisp::from_snapshot(&text), Ok(flisp.clone()));
+
+		let mut timer = Timer::default();
+		timer.write(0x83);
+		timer.tick(50);
+		flisp.mem.fb = IoDevice::Timer(timer);
+		let mut text = String::new();
+		flisp.write_snapshot(&mut text).unwrap();
+		assert_eq!(
+			text.lines().nth(4),
+			Some("fb timer 83 remaining=46 expired=true")
+		);
+		assert_eq!(Flisp::from_snapshot(&text), Ok(flisp));
+
+		assert_eq!(
+			Flisp::from_snapshot("flisp snapshot 1\nregisters A=03\nfc steppermotor 09\n"),
+			Err(FlispError::InvalidSnapshot { line: 3 })
+		);
+		assert_eq!(
+			Flisp::from_snapshot("flisp snapshot 1\nregisters A=03\nfb lamp\n"),
+			Err(FlispError::InvalidSnapshot { line: 3 })
+		);
+		assert_eq!(
+			Flisp::from_snapshot("#setMemory  00=01\n"),
+			Err(FlispError::InvalidSnapshot { line: 1 })
+		);
+		assert_eq!(
+			Flisp::from_snapshot("flisp snapshot 1\ncycles 5\n"),
+			Err(FlispError::InvalidSnapshot { line: 2 })
+		);
+	}
+}
@@ -0,0 +1,177 @@
+use std::fmt::{self, Write};
+
+use crate::{error::RecordProblem, *};
+
+/// Bytes written per S1 record
+const RECORD_LENGTH: usize = 16;
+
+/// Decodes pairs of hex digits, `None` if there is anything else or an odd number of them
+pub(crate) fn hex_bytes(digits: &str) -> Option<Vec<u8>> {
+	if digits.len() % 2 == 1 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+		return None;
+	}
+	(0..digits.len())
+		.step_by(2)
+		.map(|idx| u8::from_str_radix(&digits[idx..idx + 2], 16).ok())
+		.collect()
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+	!bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Writes one record, computing the byte count and checksum
+fn write_record<W: Write>(out: &mut W, kind: char, adr: u16, data: &[u8]) -> fmt::Result {
+	let mut bytes = vec![(data.len() + 3) as u8];
+	bytes.extend_from_slice(&adr.to_be_bytes());
+	bytes.extend_from_slice(data);
+	write!(out, "S{}", kind)?;
+	for byte in &bytes {
+		write!(out, "{:02X}", byte)?;
+	}
+	writeln!(out, "{:02X}", checksum(&bytes))
+}
+
+impl Flisp {
+	/// Loads a memory image from Motorola S-records, as written by the FLEX assembler. Memory
+	/// not covered by a record is zero. PC is taken from the reset vector, like with
+	/// [`from_str`](Flisp::from_str), not from the start address of the end record
+	pub fn from_srecords(text: &str) -> Result<Self> {
+		let mut flisp = Flisp::default();
+		let mut ended = false;
+		for (idx, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() {
+				continue;
+			}
+			let invalid = |problem| FlispError::InvalidRecord {
+				line: idx + 1,
+				problem,
+			};
+			let rest = line
+				.strip_prefix(|c| c == 'S' || c == 's')
+				.ok_or_else(|| invalid(RecordProblem::MissingStartCode))?;
+			let kind = rest
+				.chars()
+				.next()
+				.ok_or_else(|| invalid(RecordProblem::UnknownType))?;
+			let adr_length = match kind {
+				'0' | '1' | '5' | '9' => 2,
+				'2' | '6' | '8' => 3,
+				'3' | '7' => 4,
+				_ => return Err(invalid(RecordProblem::UnknownType)),
+			};
+			let bytes = hex_bytes(&rest[1..]).ok_or_else(|| invalid(RecordProblem::NotHex))?;
+			if bytes.len() < 2 + adr_length || bytes[0] as usize != bytes.len() - 1 {
+				return Err(invalid(RecordProblem::WrongLength));
+			}
+			let (stored, bytes) = bytes.split_last().unwrap();
+			let computed = checksum(bytes);
+			if *stored != computed {
+				return Err(invalid(RecordProblem::Checksum {
+					stored: *stored,
+					computed,
+				}));
+			}
+			let (adr, data) = bytes[1..].split_at(adr_length);
+			let adr = adr.iter().fold(0, |adr, byte| adr << 8 | u32::from(*byte));
+			match kind {
+				'1' | '2' | '3' => {
+					let start = adr as usize;
+					let region = flisp
+						.mem
+						.get_mut(start..start + data.len())
+						.ok_or_else(|| invalid(RecordProblem::AddressOutOfRange(adr)))?;
+					region.copy_from_slice(data);
+				}
+				'7' | '8' | '9' => {
+					ended = true;
+					break;
+				}
+				// The header and record counts carry nothing to load
+				_ => {}
+			}
+		}
+		if !ended {
+			return Err(FlispError::InvalidRecord {
+				line: text.lines().count(),
+				problem: RecordProblem::MissingEnd,
+			});
+		}
+		flisp.reset();
+		Ok(flisp)
+	}
+}
+
+impl<B: Bus> Flisp<B> {
+	/// Writes all of memory as S-records: a header, S1 records of 16 bytes and an S9 record
+	/// with the reset vector as the start address
+	pub fn write_srecords<W: Write>(&self, out: &mut W) -> fmt::Result {
+		write_record(out, '0', 0, b"flisp")?;
+		for start in (0..256).step_by(RECORD_LENGTH) {
+			let data = (start..start + RECORD_LENGTH)
+				.map(|adr| self.mem.peek(adr as u8))
+				.collect::<Vec<_>>();
+			write_record(out, '1', start as u16, &data)?;
+		}
+		write_record(out, '9', u16::from(self.mem.peek(0xFF)), &[])
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn srecords() {
+		let flisp =
+			Flisp::from_srecords("S00600004844521B\nS1050020F003E7\r\nS10400FF20DC\nS9030020DC\n")
+				.unwrap();
+		assert_eq!(flisp.mem[0x20..0x22], [0xF0, 0x03]);
+		assert_eq!(flisp.mem[0xFF], 0x20);
+		assert_eq!(flisp.PC, 0x20);
+
+		let mut text = String::new();
+		flisp.write_srecords(&mut text).unwrap();
+		assert_eq!(text.lines().count(), 18);
+		assert!(text.starts_with("S0080000"));
+		assert!(text.ends_with("S9030020DC\n"));
+		assert_eq!(Flisp::from_srecords(&text), Ok(flisp));
+
+		let error = |line, problem| Err(FlispError::InvalidRecord { line, problem });
+		assert_eq!(
+			Flisp::from_srecords("S1050020F003E8\nS9030020DC\n"),
+			error(
+				1,
+				RecordProblem::Checksum {
+					stored: 0xE8,
+					computed: 0xE7
+				}
+			)
+		);
+		assert_eq!(
+			Flisp::from_srecords("\nX1050020F003E7\n"),
+			error(2, RecordProblem::MissingStartCode)
+		);
+		assert_eq!(
+			Flisp::from_srecords("S4030020DC\n"),
+			error(1, RecordProblem::UnknownType)
+		);
+		assert_eq!(
+			Flisp::from_srecords("S1050020F003E\n"),
+			error(1, RecordProblem::NotHex)
+		);
+		assert_eq!(
+			Flisp::from_srecords("S1060020F003E6\n"),
+			error(1, RecordProblem::WrongLength)
+		);
+		assert_eq!(
+			Flisp::from_srecords("S10501FFF00307\n"),
+			error(1, RecordProblem::AddressOutOfRange(0x1FF))
+		);
+		assert_eq!(
+			Flisp::from_srecords("S1050020F003E7\n"),
+			error(1, RecordProblem::MissingEnd)
+		);
+	}
+}
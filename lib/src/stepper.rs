@@ -0,0 +1,178 @@
+/// Coil patterns in the low nibble, one half step apart going clockwise. Full stepping uses
+/// every other one, either one coil at a time or two
+const HALF_STEPS: [u8; 8] = [
+	0b0001, 0b0011, 0b0010, 0b0110, 0b0100, 0b1100, 0b1000, 0b1001,
+];
+
+/// Full steps for a whole turn of the shaft, 7.5 degrees each
+pub const FULL_STEPS_PER_TURN: i32 = 48;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Direction {
+	Stopped,
+	Clockwise,
+	CounterClockwise,
+}
+
+/// A stepper motor driven by the low nibble of the port, one bit per coil
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stepper {
+	/// The byte last written to the port
+	pub coils: u8,
+	/// Half steps turned from where the shaft started, clockwise is positive
+	pub position: i32,
+	/// Which way the last step went
+	pub direction: Direction,
+	/// Patterns three or more half steps from the last one, where a real motor may lose track
+	/// of which way to go
+	pub missed_steps: u32,
+	/// Patterns that aren't a step position, like opposing coils at once
+	pub invalid_steps: u32,
+}
+
+impl Default for Stepper {
+	fn default() -> Self {
+		Stepper {
+			coils: 0,
+			position: 0,
+			direction: Direction::Stopped,
+			missed_steps: 0,
+			invalid_steps: 0,
+		}
+	}
+}
+
+impl Stepper {
+	/// Index in `HALF_STEPS` of the pattern in the low nibble. `None` if no coils are energised
+	/// or the pattern isn't a step position
+	fn phase(coils: u8) -> Option<usize> {
+		HALF_STEPS
+			.iter()
+			.position(|pattern| *pattern == coils & 0x0F)
+	}
+
+	/// Energises the coils in the low nibble of `val` and turns the shaft to line up with them
+	pub fn write(&mut self, val: u8) {
+		let old = Self::phase(self.coils);
+		self.coils = val;
+		if val & 0x0F == 0 {
+			// Unpowered, the shaft stays where it is
+			return;
+		}
+		let new = match Self::phase(val) {
+			Some(new) => new,
+			None => {
+				self.invalid_steps += 1;
+				return;
+			}
+		};
+		// Coming from no or an invalid pattern the shaft snaps into place, that isn't counted
+		let old = match old {
+			Some(old) => old,
+			None => return,
+		};
+		let delta = match (new + HALF_STEPS.len() - old) % HALF_STEPS.len() {
+			0 => return,
+			// Half or full steps either way
+			delta @ 1..=2 => delta as i32,
+			delta @ 6..=7 => delta as i32 - 8,
+			// Too far to tell, assume the shorter way round and clockwise when it's a tie
+			delta => {
+				self.missed_steps += 1;
+				if delta <= 4 {
+					delta as i32
+				} else {
+					delta as i32 - 8
+				}
+			}
+		};
+		self.position += delta;
+		self.direction = if delta > 0 {
+			Direction::Clockwise
+		} else {
+			Direction::CounterClockwise
+		};
+	}
+
+	/// Shaft angle in degrees from where it started, within one turn
+	pub fn angle(&self) -> f64 {
+		let half_steps = FULL_STEPS_PER_TURN * 2;
+		f64::from(self.position.rem_euclid(half_steps)) * 360.0 / f64::from(half_steps)
+	}
+
+	/// Whole turns made, counter clockwise ones are negative
+	pub fn turns(&self) -> i32 {
+		self.position.div_euclid(FULL_STEPS_PER_TURN * 2)
+	}
+}
+
+impl Direction {
+	/// Name used in snapshots
+	pub fn name(&self) -> &'static str {
+		match self {
+			Direction::Stopped => "stopped",
+			Direction::Clockwise => "cw",
+			Direction::CounterClockwise => "ccw",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		let res = match name {
+			"stopped" => Direction::Stopped,
+			"cw" => Direction::Clockwise,
+			"ccw" => Direction::CounterClockwise,
+			_ => return None,
+		};
+		Some(res)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn stepping() {
+		let mut motor = Stepper::default();
+		// Full steps with one coil, then half steps back
+		for coils in [0b0001, 0b0010, 0b0100, 0b1000, 0b0001].iter() {
+			motor.write(*coils);
+		}
+		assert_eq!(motor.position, 8);
+		assert_eq!(motor.direction, Direction::Clockwise);
+		for coils in [0b1001, 0b1000, 0b1100].iter() {
+			motor.write(*coils);
+		}
+		assert_eq!(motor.position, 5);
+		assert_eq!(motor.direction, Direction::CounterClockwise);
+		assert_eq!((motor.missed_steps, motor.invalid_steps), (0, 0));
+
+		// Opposing coils
+		motor.write(0b0101);
+		assert_eq!(motor.invalid_steps, 1);
+		assert_eq!(motor.position, 5);
+
+		// Three half steps ahead
+		motor.write(0b1100);
+		motor.write(0b0001);
+		assert_eq!(motor.missed_steps, 1);
+		assert_eq!(motor.position, 8);
+
+		// Turning off and on again realigns without moving
+		motor.write(0);
+		motor.write(0b0100);
+		assert_eq!(motor.position, 8);
+
+		let mut motor = Stepper::default();
+		motor.write(0b0001);
+		for _ in 0..FULL_STEPS_PER_TURN / 4 {
+			for coils in [0b1000, 0b0100, 0b0010, 0b0001].iter() {
+				motor.write(*coils);
+			}
+		}
+		assert_eq!(motor.turns(), -1);
+		assert_eq!(motor.angle(), 0.0);
+		motor.write(0b1001);
+		assert_eq!(motor.angle(), 356.25);
+	}
+}
@@ -0,0 +1,101 @@
+/// Clock cycles per tick of the timer
+pub const CYCLES_PER_TICK: u16 = 16;
+
+/// Set in the control byte to interrupt the processor when the period has elapsed
+pub const IRQ_ENABLE: u8 = 0x80;
+
+/// Set in the status byte once the period has elapsed, until the control byte is written
+pub const EXPIRED: u8 = 0x80;
+
+/// A timer counting clock cycles. Writing the port sets the control byte, the period in ticks
+/// in the low seven bits and [`IRQ_ENABLE`] in the top one. A period of 0 stops it. Reading
+/// gives the status byte, [`EXPIRED`] and the ticks left of the current period
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Timer {
+	/// The byte last written to the port
+	pub control: u8,
+	/// Clock cycles left until the period has elapsed
+	pub remaining: u16,
+	/// Whether the period has elapsed since the control byte was last written
+	pub expired: bool,
+}
+
+impl Timer {
+	/// The period in clock cycles, 0 if the timer is stopped
+	pub fn period(&self) -> u16 {
+		u16::from(self.control & !IRQ_ENABLE) * CYCLES_PER_TICK
+	}
+
+	/// The status byte seen by the processor
+	pub fn status(&self) -> u8 {
+		let ticks = self.remaining.div_ceil(CYCLES_PER_TICK);
+		let expired = if self.expired { EXPIRED } else { 0 };
+		expired | ticks as u8
+	}
+
+	/// Sets the control byte and acknowledges an elapsed period. The count only restarts if
+	/// the period changes, so a handler rewriting the same control byte doesn't drift
+	pub fn write(&mut self, val: u8) {
+		let old = self.period();
+		self.control = val;
+		self.expired = false;
+		if self.period() != old {
+			self.remaining = self.period();
+		}
+	}
+
+	/// Counts down `cycles` clock cycles, starting over each time the period elapses
+	pub fn tick(&mut self, cycles: u8) {
+		let period = self.period();
+		if period == 0 {
+			return;
+		}
+		let mut cycles = u16::from(cycles);
+		while cycles >= self.remaining {
+			cycles -= self.remaining;
+			self.remaining = period;
+			self.expired = true;
+		}
+		self.remaining -= cycles;
+	}
+
+	/// Whether the timer is holding the interrupt line
+	pub fn irq(&self) -> bool {
+		self.expired && self.control & IRQ_ENABLE != 0
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn counting() {
+		let mut timer = Timer::default();
+		timer.tick(200);
+		assert_eq!(timer, Timer::default());
+
+		// Two ticks, polled
+		timer.write(2);
+		assert_eq!(timer.status(), 2);
+		timer.tick(20);
+		assert_eq!(timer.status(), 1);
+		assert!(!timer.expired);
+		timer.tick(12);
+		assert_eq!(timer.status(), EXPIRED | 2);
+		assert!(!timer.irq());
+
+		// Acknowledging with the same period keeps counting
+		timer.tick(5);
+		timer.write(IRQ_ENABLE | 2);
+		assert_eq!(timer.remaining, 27);
+		timer.tick(100);
+		assert!(timer.irq());
+		assert_eq!(timer.remaining, 23);
+
+		timer.write(0);
+		assert!(!timer.irq());
+		timer.tick(100);
+		assert_eq!(timer.status(), 0);
+	}
+}
@@ -0,0 +1,254 @@
+use std::fmt::{self, Write};
+
+use crate::*;
+
+/// The programmer visible registers at one point in time
+#[allow(non_snake_case)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Registers {
+	pub A: u8,
+	pub X: u8,
+	pub Y: u8,
+	pub SP: u8,
+	pub PC: u8,
+	pub CC: u8,
+}
+
+impl Registers {
+	pub fn of<B: Bus>(flisp: &Flisp<B>) -> Self {
+		Registers {
+			A: flisp.A,
+			X: flisp.X,
+			Y: flisp.Y,
+			SP: flisp.SP,
+			PC: flisp.PC,
+			CC: flisp.CC,
+		}
+	}
+
+	const NAMES: [&'static str; 6] = ["A", "X", "Y", "SP", "PC", "CC"];
+
+	/// Names and values in the order of `NAMES`
+	fn fields(&self) -> impl Iterator<Item = (&'static str, u8)> {
+		let values = [self.A, self.X, self.Y, self.SP, self.PC, self.CC];
+		Self::NAMES.iter().copied().zip(values.to_vec())
+	}
+}
+
+impl fmt::Display for Registers {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut sep = "";
+		for (name, value) in self.fields() {
+			write!(f, "{}{}={:02X}", sep, name, value)?;
+			sep = " ";
+		}
+		Ok(())
+	}
+}
+
+/// One executed instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+	/// Value of the cycle counter before the instruction
+	pub cycle: u64,
+	pub pc: u8,
+	/// Disassembly of the instruction, or `IRQ` if an interrupt was serviced instead
+	pub text: String,
+	pub cycles: u8,
+	pub before: Registers,
+	pub after: Registers,
+	/// Address and value of every memory write, in order
+	pub writes: Vec<(u8, u8)>,
+}
+
+impl fmt::Display for TraceEntry {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{:02X}  {:<16}{}  ->  {}",
+			self.pc, self.text, self.before, self.after
+		)?;
+		for (adr, value) in &self.writes {
+			write!(f, "  ${:02X}={:02X}", adr, value)?;
+		}
+		Ok(())
+	}
+}
+
+/// Records every step taken through it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+	entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn entries(&self) -> &[TraceEntry] {
+		&self.entries
+	}
+
+	pub fn clear(&mut self) {
+		self.entries.clear();
+	}
+
+	/// Steps and records what the step did
+	pub fn step<B: Bus>(&mut self, flisp: &mut Flisp<B>) -> u8 {
+		let before = Registers::of(flisp);
+		let cycle = flisp.cycles;
+		let mut text = String::new();
+		if flisp.irq_pending() {
+			text.push_str("IRQ");
+		} else {
+			// Formatting into a String can't fail
+			let _ = flisp.print_disassembly(&mut text, flisp.PC);
+		}
+		let cycles = flisp.step();
+		let writes = flisp
+			.accesses
+			.iter()
+			.filter(|access| matches!(access.kind, AccessKind::Write { .. }))
+			.map(|access| (access.adr, access.value))
+			.collect();
+		self.entries.push(TraceEntry {
+			cycle,
+			pc: before.PC,
+			text: text.trim_end().to_string(),
+			cycles,
+			before,
+			after: Registers::of(flisp),
+			writes,
+		});
+		cycles
+	}
+
+	/// One line per instruction, for reading
+	pub fn write_text<W: Write>(&self, out: &mut W) -> fmt::Result {
+		for entry in &self.entries {
+			writeln!(out, "{}", entry)?;
+		}
+		Ok(())
+	}
+
+	/// A header and one row per instruction. Numbers are decimal, writes are `adr:value`
+	/// pairs separated by spaces
+	pub fn write_csv<W: Write>(&self, out: &mut W) -> fmt::Result {
+		write!(out, "cycle,pc,instruction,cycles")?;
+		for name in Registers::NAMES.iter() {
+			write!(out, ",{}", name)?;
+		}
+		for name in Registers::NAMES.iter() {
+			write!(out, ",{}_after", name)?;
+		}
+		writeln!(out, ",writes")?;
+		for entry in &self.entries {
+			write!(
+				out,
+				"{},{},\"{}\",{}",
+				entry.cycle,
+				entry.pc,
+				compact(&entry.text).replace('"', "\"\""),
+				entry.cycles
+			)?;
+			for (_, value) in entry.before.fields().chain(entry.after.fields()) {
+				write!(out, ",{}", value)?;
+			}
+			let writes = entry
+				.writes
+				.iter()
+				.map(|(adr, value)| format!("{}:{}", adr, value))
+				.collect::<Vec<_>>();
+			writeln!(out, ",{}", writes.join(" "))?;
+		}
+		Ok(())
+	}
+
+	/// One JSON object per line and instruction
+	pub fn write_json_lines<W: Write>(&self, out: &mut W) -> fmt::Result {
+		let registers = |regs: &Registers| {
+			let fields = regs
+				.fields()
+				.map(|(name, value)| format!("\"{}\":{}", name, value))
+				.collect::<Vec<_>>();
+			format!("{{{}}}", fields.join(","))
+		};
+		for entry in &self.entries {
+			let writes = entry
+				.writes
+				.iter()
+				.map(|(adr, value)| format!("{{\"adr\":{},\"value\":{}}}", adr, value))
+				.collect::<Vec<_>>();
+			writeln!(
+				out,
+				"{{\"cycle\":{},\"pc\":{},\"instruction\":\"{}\",\"cycles\":{},\"before\":{},\"after\":{},\"writes\":[{}]}}",
+				entry.cycle,
+				entry.pc,
+				compact(&entry.text).replace('\\', "\\\\").replace('"', "\\\""),
+				entry.cycles,
+				registers(&entry.before),
+				registers(&entry.after),
+				writes.join(",")
+			)?;
+		}
+		Ok(())
+	}
+}
+
+/// Disassembly with the column padding collapsed, `LDA #$03`
+fn compact(text: &str) -> String {
+	text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn export() {
+		let mut flisp = assemble(
+			"\
+	ORG	$20
+	LDA	#3
+	STA	$50,X
+	ORG	$FF
+	FCB	$20
+",
+		)
+		.unwrap();
+		let mut trace = Trace::new();
+		trace.step(&mut flisp);
+		trace.step(&mut flisp);
+		assert_eq!(trace.entries().len(), 2);
+		assert_eq!(trace.entries()[1].writes, [(0x50, 3)]);
+
+		let mut text = String::new();
+		trace.write_text(&mut text).unwrap();
+		assert_eq!(
+			text.lines().collect::<Vec<_>>(),
+			[
+				"20  LDA     #$03    A=00 X=00 Y=00 SP=00 PC=20 CC=10  ->  A=03 X=00 Y=00 SP=00 PC=22 CC=10",
+				"22  STA     $50,X   A=03 X=00 Y=00 SP=00 PC=22 CC=10  ->  A=03 X=00 Y=00 SP=00 PC=24 CC=10  $50=03",
+			]
+		);
+
+		let mut csv = String::new();
+		trace.write_csv(&mut csv).unwrap();
+		assert_eq!(
+			csv.lines().collect::<Vec<_>>(),
+			[
+				"cycle,pc,instruction,cycles,A,X,Y,SP,PC,CC,A_after,X_after,Y_after,SP_after,PC_after,CC_after,writes",
+				"0,32,\"LDA #$03\",2,0,0,0,0,32,16,3,0,0,0,34,16,",
+				"2,34,\"STA $50,X\",3,3,0,0,0,34,16,3,0,0,0,36,16,80:3",
+			]
+		);
+
+		let mut json = String::new();
+		trace.write_json_lines(&mut json).unwrap();
+		assert_eq!(
+			json.lines().nth(1),
+			Some("{\"cycle\":2,\"pc\":34,\"instruction\":\"STA $50,X\",\"cycles\":3,\"before\":{\"A\":3,\"X\":0,\"Y\":0,\"SP\":0,\"PC\":34,\"CC\":16},\"after\":{\"A\":3,\"X\":0,\"Y\":0,\"SP\":0,\"PC\":36,\"CC\":16},\"writes\":[{\"adr\":80,\"value\":3}]}")
+		);
+	}
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+
+use crate::*;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WatchKind {
+	/// Any read other than an instruction fetch
+	Read,
+	Write,
+	/// A write that stores a different value than was there
+	Change,
+}
+
+/// Triggers when an instruction accesses an address in `start..=end`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Watchpoint {
+	pub start: u8,
+	pub end: u8,
+	pub kind: WatchKind,
+}
+
+impl Watchpoint {
+	pub fn new(start: u8, end: u8, kind: WatchKind) -> Self {
+		Watchpoint { start, end, kind }
+	}
+
+	/// Watches a single address
+	pub fn at(adr: u8, kind: WatchKind) -> Self {
+		Self::new(adr, adr, kind)
+	}
+
+	pub fn triggered_by(&self, access: &Access) -> bool {
+		let kind_matches = match (self.kind, access.kind) {
+			(WatchKind::Read, AccessKind::Read) => true,
+			(WatchKind::Write, AccessKind::Write { .. }) => true,
+			(WatchKind::Change, AccessKind::Write { old }) => old != access.value,
+			_ => false,
+		};
+		kind_matches && (self.start..=self.end).contains(&access.adr)
+	}
+}
+
+impl fmt::Display for Watchpoint {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "${:02X}", self.start)?;
+		if self.end != self.start {
+			write!(f, "-${:02X}", self.end)?;
+		}
+		write!(f, " {:?}", self.kind)
+	}
+}
+
+/// An access that triggered a watchpoint
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WatchHit {
+	pub watchpoint: Watchpoint,
+	pub access: Access,
+	/// Address of the instruction that made the access
+	pub pc: u8,
+}
+
+impl fmt::Display for WatchHit {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.access.kind {
+			AccessKind::Write { old } => write!(
+				f,
+				"${:02X} written ${:02X} (was ${:02X})",
+				self.access.adr, self.access.value, old
+			)?,
+			_ => write!(
+				f,
+				"${:02X} read ${:02X}",
+				self.access.adr, self.access.value
+			)?,
+		}
+		write!(f, " by the instruction at ${:02X}", self.pc)
+	}
+}
+
+/// Addresses and ranges to watch for accesses
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Watchpoints {
+	points: Vec<Watchpoint>,
+}
+
+impl Watchpoints {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn add(&mut self, watchpoint: Watchpoint) {
+		if !self.points.contains(&watchpoint) {
+			self.points.push(watchpoint);
+		}
+	}
+
+	/// Removes the watchpoint at `idx` in the order they were added
+	pub fn remove(&mut self, idx: usize) -> Option<Watchpoint> {
+		if idx < self.points.len() {
+			Some(self.points.remove(idx))
+		} else {
+			None
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.points.clear();
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.points.is_empty()
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &Watchpoint> {
+		self.points.iter()
+	}
+
+	/// Every watchpoint triggered by `accesses`, made by the instruction at `pc`
+	pub fn check(&self, pc: u8, accesses: &[Access]) -> Vec<WatchHit> {
+		accesses
+			.iter()
+			.flat_map(|access| {
+				self.points
+					.iter()
+					.filter(move |watchpoint| watchpoint.triggered_by(access))
+					.map(move |watchpoint| WatchHit {
+						watchpoint: *watchpoint,
+						access: *access,
+						pc,
+					})
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn watch() {
+		let mut flisp = assemble(
+			"\
+	ORG	$20
+	LDA	#7
+	STA	$50
+	STA	$50
+	LDA	$41
+	PSHA
+	ORG	$FF
+	FCB	$20
+",
+		)
+		.unwrap();
+		flisp.SP = 0x48;
+		let mut watchpoints = Watchpoints::new();
+		watchpoints.add(Watchpoint::at(0x50, WatchKind::Change));
+		watchpoints.add(Watchpoint::new(0x40, 0x4F, WatchKind::Read));
+		watchpoints.add(Watchpoint::new(0x40, 0x4F, WatchKind::Write));
+
+		// Fetching the operand of LDA #7 isn't a read
+		assert!(flisp.step_watched(&watchpoints).is_empty());
+
+		let hits = flisp.step_watched(&watchpoints);
+		assert_eq!(hits.len(), 1);
+		assert_eq!(hits[0].pc, 0x22);
+		assert_eq!(
+			hits[0].to_string(),
+			"$50 written $07 (was $00) by the instruction at $22"
+		);
+
+		// Same value again, not a change
+		assert!(flisp.step_watched(&watchpoints).is_empty());
+
+		let hits = flisp.step_watched(&watchpoints);
+		assert_eq!(hits.len(), 1);
+		assert_eq!(hits[0].access.kind, AccessKind::Read);
+
+		// The push lands in the watched range
+		let hits = flisp.step_watched(&watchpoints);
+		assert_eq!(hits.len(), 1);
+		assert_eq!(hits[0].access.adr, 0x47);
+		assert_eq!(hits[0].watchpoint.kind, WatchKind::Write);
+	}
+}
@@ -0,0 +1,290 @@
+use std::{convert::TryFrom, env, fmt, fmt::Write, fs, process, str::FromStr};
+
+use flisp_lib::{
+	assembler, trace::Registers, Breakpoints, Flisp, IllegalOpcodePolicy, Limits, LoopDetector,
+	StopReason,
+};
+
+const USAGE: &str = "\
+Usage: flisp_run [OPTIONS] FILE
+
+Runs a memory image (.fmem, .s19, .hex) or assembly source (.sflisp, .asm) without a user
+interface and prints the final state.
+
+Options:
+    --until ADR        Stop when PC reaches ADR
+    --max-steps N      Stop after N instructions (default 1000000)
+    --max-cycles N     Stop after N clock cycles
+    --format FORMAT    json (default) or text
+    --halt-on-illegal  Stop at an illegal opcode instead of trapping through $FD
+
+Exit status:
+    0  Reached --until, or the program halted on BRA * or JMP *
+    1  Bad arguments or input file
+    2  Hit the step or cycle limit
+    3  Stuck in an infinite loop
+    4  Halted on an illegal opcode";
+
+/// Steps kept to look for repetition
+const LOOP_WINDOW: usize = 1024;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Format {
+	Json,
+	Text,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+	file: String,
+	until: Option<u8>,
+	max_steps: u64,
+	max_cycles: Option<u64>,
+	format: Format,
+	halt_on_illegal: bool,
+}
+
+fn exit_code(reason: StopReason) -> i32 {
+	match reason {
+		StopReason::Breakpoint(_) | StopReason::Halted(_) => 0,
+		StopReason::StepLimit | StopReason::CycleLimit => 2,
+		StopReason::InfiniteLoop { .. } => 3,
+		StopReason::IllegalOpcode { .. } => 4,
+	}
+}
+
+/// How the reason is spelled in the report
+fn stop_name(reason: StopReason) -> &'static str {
+	match reason {
+		StopReason::Breakpoint(_) => "address",
+		StopReason::Halted(_) => "halted",
+		StopReason::StepLimit => "step_limit",
+		StopReason::CycleLimit => "cycle_limit",
+		StopReason::InfiniteLoop { .. } => "infinite_loop",
+		StopReason::IllegalOpcode { .. } => "illegal_opcode",
+	}
+}
+
+fn parse_number<T: TryFrom<u64>>(s: &str) -> Option<T> {
+	let num = if let Some(hex) = s.strip_prefix('$').or_else(|| s.strip_prefix("0x")) {
+		u64::from_str_radix(hex, 16).ok()?
+	} else {
+		s.parse().ok()?
+	};
+	T::try_from(num).ok()
+}
+
+/// Returns `None` when help is asked for
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+	let mut file = None;
+	let mut options = Options {
+		file: String::new(),
+		until: None,
+		max_steps: 1_000_000,
+		max_cycles: None,
+		format: Format::Json,
+		halt_on_illegal: false,
+	};
+	while let Some(arg) = args.next() {
+		let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+		match arg.as_str() {
+			"--until" => {
+				let value = value()?;
+				options.until =
+					Some(parse_number(&value).ok_or(format!("Invalid address {}", value))?);
+			}
+			"--max-steps" => {
+				let value = value()?;
+				options.max_steps =
+					parse_number(&value).ok_or(format!("Invalid step count {}", value))?;
+			}
+			"--max-cycles" => {
+				let value = value()?;
+				options.max_cycles =
+					Some(parse_number(&value).ok_or(format!("Invalid cycle count {}", value))?);
+			}
+			"--format" =>
+				options.format = match value()?.as_str() {
+					"json" => Format::Json,
+					"text" => Format::Text,
+					other => return Err(format!("Unknown format {}", other)),
+				},
+			"--halt-on-illegal" => options.halt_on_illegal = true,
+			"-h" | "--help" => return Ok(None),
+			_ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+			_ if file.is_none() => file = Some(arg),
+			_ => return Err(format!("Unexpected argument {}", arg)),
+		}
+	}
+	options.file = file.ok_or("Missing input file")?;
+	Ok(Some(options))
+}
+
+fn load(path: &str) -> Result<Flisp, String> {
+	let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+	if path.ends_with(".sflisp") || path.ends_with(".asm") {
+		assembler::assemble(&source).map_err(|e| e.in_file(path).to_string())
+	} else if path.ends_with(".s19") || path.ends_with(".srec") {
+		Flisp::from_srecords(&source).map_err(|e| format!("{}: {}", path, e))
+	} else if path.ends_with(".hex") || path.ends_with(".ihx") {
+		Flisp::from_intel_hex(&source).map_err(|e| format!("{}: {}", path, e))
+	} else {
+		Flisp::from_str(&source).map_err(|e| format!("{}: {}", path, e))
+	}
+}
+
+fn run(flisp: &mut Flisp, options: &Options) -> (StopReason, u64) {
+	let mut breakpoints = Breakpoints::new();
+	if let Some(adr) = options.until {
+		breakpoints.set(adr, None);
+	}
+	if options.halt_on_illegal {
+		flisp.on_illegal_opcode = IllegalOpcodePolicy::Halt;
+	}
+	let limits = Limits {
+		max_steps: Some(options.max_steps),
+		max_cycles: options.max_cycles,
+	};
+	flisp.run(&breakpoints, &mut LoopDetector::new(LOOP_WINDOW), limits)
+}
+
+fn report(
+	flisp: &Flisp,
+	stop: StopReason,
+	steps: u64,
+	format: Format,
+) -> Result<String, fmt::Error> {
+	let mut out = String::new();
+	let regs = Registers::of(flisp);
+	match format {
+		Format::Json => {
+			let memory = flisp
+				.mem
+				.iter()
+				.map(ToString::to_string)
+				.collect::<Vec<_>>();
+			writeln!(
+				out,
+				"{{\"stop\":\"{}\",\"steps\":{},\"cycles\":{},\"registers\":{{\"A\":{},\"X\":{},\"Y\":{},\"SP\":{},\"PC\":{},\"CC\":{}}},\"memory\":[{}]}}",
+				stop_name(stop),
+				steps,
+				flisp.cycles,
+				regs.A,
+				regs.X,
+				regs.Y,
+				regs.SP,
+				regs.PC,
+				regs.CC,
+				memory.join(",")
+			)?;
+		}
+		Format::Text => {
+			writeln!(out, "stop: {}", stop_name(stop))?;
+			writeln!(out, "reason: {}", stop)?;
+			writeln!(out, "steps: {}", steps)?;
+			writeln!(out, "cycles: {}", flisp.cycles)?;
+			writeln!(out, "{}", regs)?;
+			for (row, bytes) in flisp.mem.chunks(16).enumerate() {
+				write!(out, "{:02X}:", row * 16)?;
+				for byte in bytes {
+					write!(out, " {:02X}", byte)?;
+				}
+				writeln!(out)?;
+			}
+		}
+	}
+	Ok(out)
+}
+
+fn main() {
+	let options = match parse_args(env::args().skip(1)) {
+		Ok(Some(options)) => options,
+		Ok(None) => {
+			println!("{}", USAGE);
+			process::exit(0);
+		}
+		Err(e) => {
+			eprintln!("{}\n\n{}", e, USAGE);
+			process::exit(1);
+		}
+	};
+	let mut flisp = match load(&options.file) {
+		Ok(flisp) => flisp,
+		Err(e) => {
+			eprintln!("{}", e);
+			process::exit(1);
+		}
+	};
+
+	let (stop, steps) = run(&mut flisp, &options);
+	match report(&flisp, stop, steps, options.format) {
+		Ok(out) => print!("{}", out),
+		Err(e) => {
+			eprintln!("{}", e);
+			process::exit(1);
+		}
+	}
+	process::exit(exit_code(stop));
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn options(args: &str) -> Result<Option<Options>, String> {
+		parse_args(args.split_whitespace().map(String::from))
+	}
+
+	#[test]
+	fn args() {
+		let opts = options("--until $4A --max-cycles 100 --format text prog.sflisp")
+			.unwrap()
+			.unwrap();
+		assert_eq!(opts.file, "prog.sflisp");
+		assert_eq!(opts.until, Some(0x4A));
+		assert_eq!(opts.max_cycles, Some(100));
+		assert_eq!(opts.format, Format::Text);
+		assert!(options("--until 300 prog.sflisp").is_err());
+		assert!(options("--until").is_err());
+		assert!(options("").is_err());
+		assert_eq!(options("prog.sflisp --help"), Ok(None));
+	}
+
+	#[test]
+	fn stops() {
+		let program = |source| assembler::assemble(source).unwrap();
+		let opts = options("x --max-steps 100").unwrap().unwrap();
+
+		let mut flisp = program("\tLDA\t#1\n\tBRA\t*\n");
+		assert_eq!(run(&mut flisp, &opts), (StopReason::Halted(2), 1));
+
+		// A wraps around after 256 times through the loop
+		let mut flisp = program("loop:\tINCA\n\tBRA\tloop\n");
+		let long = Options {
+			max_steps: 1000,
+			..opts.clone()
+		};
+		assert_eq!(
+			run(&mut flisp, &long),
+			(StopReason::InfiniteLoop { pc: 1, period: 512 }, 513)
+		);
+
+		// Changes memory every time around, so never repeats
+		let mut flisp = program("loop:\tINC\t$80\n\tINC\t$81\n\tBRA\tloop\n");
+		assert_eq!(run(&mut flisp, &opts), (StopReason::StepLimit, 100));
+
+		let mut flisp = program("\tNOP\n\tNOP\n\tNOP\n\tBRA\t*\n");
+		let opts = Options {
+			until: Some(2),
+			..opts
+		};
+		assert_eq!(run(&mut flisp, &opts), (StopReason::Breakpoint(2), 2));
+
+		let mut flisp = program("\tNOP\n\tFCB\t$03\n");
+		let opts = options("x --halt-on-illegal").unwrap().unwrap();
+		assert_eq!(
+			run(&mut flisp, &opts),
+			(StopReason::IllegalOpcode { addr: 1, byte: 3 }, 2)
+		);
+	}
+}
@@ -9,6 +9,11 @@ pub(crate) enum RunTimeError {
 	MalformedArgument,
 	BadFilePath,
 	BadFile,
+	NoSuchBreakpoint,
+	NoSuchWatchpoint,
+	HistoryExhausted,
+	NotTracing,
+	NoInputDevice,
 }
 
 impl fmt::Display for RunTimeError {
@@ -16,11 +21,16 @@ impl fmt::Display for RunTimeError {
 		let s = match self {
 			RunTimeError::InvalidDeviceType => "Invalid IO device type",
 			RunTimeError::InvalidIOPort => "Invalid IO port",
-			RunTimeError::InvalidCommand => "Invalid command\n   Type \"help\" or \"?\" for help",
+			RunTimeError::InvalidCommand => "Invalid command\nType \"help\" or \"?\" for help",
 			RunTimeError::MissingArgument => "Missing argument",
 			RunTimeError::MalformedArgument => "Malformed argument or unparsable number",
 			RunTimeError::BadFilePath => "Cannot find file specified",
 			RunTimeError::BadFile => "Error while loading file",
+			RunTimeError::NoSuchBreakpoint => "No breakpoint at that address",
+			RunTimeError::NoSuchWatchpoint => "No watchpoint with that number",
+			RunTimeError::HistoryExhausted => "Not found in the execution history",
+			RunTimeError::NotTracing => "Tracing is off, turn it on with \"trace on\"",
+			RunTimeError::NoInputDevice => "No device of that kind attached, add one with \"io\"",
 		};
 		write!(f, "{}", s)
 	}
@@ -1,31 +1,187 @@
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub(crate) enum IoDevice {
-	Nothing,
-	Bargraph(u8),
-	HexDisplay(u8),
-	SevenSeg(u8),
-	StepperMotor(u8),
-	DILSwitch(u8),
-	KeyPad(u8),
-	IRQFlipFlop(u8),
-}
-
-impl IoDevice {
-	pub(crate) fn read(&self) -> Option<u8> {
-		let res = match self {
-			IoDevice::Nothing => return None,
-			IoDevice::Bargraph(_) => 0,
-			IoDevice::HexDisplay(stored) => *stored,
-			IoDevice::SevenSeg(stored) => *stored,
-			IoDevice::StepperMotor(stored) => *stored,
-			IoDevice::DILSwitch(stored) => *stored,
-			IoDevice::KeyPad(stored) => *stored,
-			IoDevice::IRQFlipFlop(stored) => *stored,
-		};
-		Some(res)
+use flisp_lib::{timer::IRQ_ENABLE, IoDevice, KEY_READY};
+use tui::{
+	style::{Color, Modifier, Style},
+	text::{Span, Spans},
+	widgets::{Block, BorderType, Borders, Paragraph},
+};
+
+/// Segments lit for each hex digit
+const HEX_SEGMENTS: [u8; 16] = [
+	0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07, 0x7F, 0x6F, 0x77, 0x7C, 0x39, 0x5E, 0x79, 0x71,
+];
+
+/// Three rows of segment art. Bit 0 is segment a, going clockwise to f in bit 5, then g in
+/// bit 6 and the decimal point in bit 7
+fn segments(bits: u8) -> [String; 3] {
+	let seg = |bit: u8, c: char| if bits & (1 << bit) != 0 { c } else { ' ' };
+	[
+		format!(" {}  ", seg(0, '_')),
+		format!("{}{}{} ", seg(5, '|'), seg(6, '_'), seg(1, '|')),
+		format!(
+			"{}{}{}{}",
+			seg(4, '|'),
+			seg(3, '_'),
+			seg(2, '|'),
+			seg(7, '.')
+		),
+	]
+}
+
+/// Digits side by side, lit in red
+fn digits(digits: &[u8]) -> Vec<Spans<'static>> {
+	let art = digits
+		.iter()
+		.map(|bits| segments(*bits))
+		.collect::<Vec<_>>();
+	(0..3)
+		.map(|row| {
+			let text = art
+				.iter()
+				.map(|digit| digit[row].as_str())
+				.collect::<Vec<_>>()
+				.join(" ");
+			Spans::from(Span::styled(text, Style::default().fg(Color::Red)))
+		})
+		.collect()
+}
+
+/// One lamp per bit, most significant first, under the bit numbers
+fn lamps(value: u8, on: &'static str, off: &'static str, color: Color) -> Vec<Spans<'static>> {
+	let numbers = (0..8)
+		.rev()
+		.map(|bit| bit.to_string())
+		.collect::<Vec<_>>()
+		.join(" ");
+	let lamps = (0..8)
+		.rev()
+		.flat_map(|bit| {
+			let lamp = if value & (1 << bit) != 0 {
+				Span::styled(on, Style::default().fg(color))
+			} else {
+				Span::styled(off, Style::default().fg(Color::DarkGray))
+			};
+			vec![lamp, Span::raw(" ")]
+		})
+		.collect::<Vec<_>>();
+	vec![Spans::from(numbers), Spans::from(lamps)]
+}
+
+/// A red lamp and `on` when set, otherwise a dark one and `off`
+fn indicator(set: bool, on: &'static str, off: &'static str) -> Spans<'static> {
+	if set {
+		Spans::from(vec![
+			Span::styled("●", Style::default().fg(Color::Red)),
+			Span::raw(format!(" {}", on)),
+		])
+	} else {
+		Spans::from(vec![
+			Span::styled("○", Style::default().fg(Color::DarkGray)),
+			Span::raw(format!(" {}", off)),
+		])
 	}
+}
 
-	pub(crate) fn to_widget<W: tui::widgets::Widget>(&self) -> Box<W> {
-		todo!()
+/// An arrow pointing the way the shaft does, to the nearest 45 degrees
+fn shaft_arrow(angle: f64) -> &'static str {
+	const ARROWS: [&str; 8] = ["↑", "↗", "→", "↘", "↓", "↙", "←", "↖"];
+	ARROWS[((angle + 22.5) / 45.0) as usize % ARROWS.len()]
+}
+
+pub(crate) trait DeviceWidget {
+	/// What the device shows, one entry per row
+	fn rows(&self) -> Vec<Spans<'static>>;
+
+	/// The rows in a panel titled with the port and the kind of device
+	fn to_widget(&self, port: &str) -> Paragraph<'static>;
+}
+
+impl DeviceWidget for IoDevice {
+	fn rows(&self) -> Vec<Spans<'static>> {
+		match *self {
+			IoDevice::Nothing => Vec::new(),
+			IoDevice::Bargraph(value) => lamps(value, "●", "○", Color::Red),
+			IoDevice::HexDisplay(value) => digits(&[
+				HEX_SEGMENTS[(value >> 4) as usize],
+				HEX_SEGMENTS[(value & 0x0F) as usize],
+			]),
+			IoDevice::SevenSeg(value) => digits(&[value]),
+			IoDevice::StepperMotor(motor) => vec![
+				Spans::from(vec![
+					Span::styled(
+						shaft_arrow(motor.angle()),
+						Style::default().fg(Color::Yellow),
+					),
+					Span::raw(format!(" Coils {:04b}", motor.coils & 0x0F)),
+				]),
+				Spans::from(format!(
+					"{:5.1}° {} Turns {}",
+					motor.angle(),
+					motor.direction.name(),
+					motor.turns()
+				)),
+				Spans::from(format!(
+					"Missed {} Invalid {}",
+					motor.missed_steps, motor.invalid_steps
+				)),
+			],
+			IoDevice::DILSwitch(value) => lamps(value, "▲", "▼", Color::Green),
+			IoDevice::KeyPad(value) => vec![Spans::from(format!("Key ${:02X}", value))],
+			IoDevice::IRQFlipFlop(value) => vec![indicator(value != 0, "Interrupting", "Clear")],
+			IoDevice::Timer(timer) => vec![
+				indicator(timer.expired, "Expired", "Counting"),
+				Spans::from(format!(
+					"Period {} Left {}",
+					timer.period(),
+					timer.remaining
+				)),
+				Spans::from(if timer.control & IRQ_ENABLE != 0 {
+					"Interrupts on"
+				} else {
+					"Interrupts off"
+				}),
+			],
+		}
 	}
+
+	fn to_widget(&self, port: &str) -> Paragraph<'static> {
+		Paragraph::new(self.rows()).block(
+			Block::default()
+				.borders(Borders::ALL)
+				.border_type(BorderType::Rounded)
+				.title(format!("{} {}", port, self.name())),
+		)
+	}
+}
+
+/// Size of the keypad overlay, border included
+pub(crate) const KEYPAD_WIDTH: u16 = 14;
+pub(crate) const KEYPAD_HEIGHT: u16 = 6;
+
+/// The 16 keys in a grid with the last one entered highlighted, brighter while its press
+/// hasn't been read
+pub(crate) fn keypad_overlay(value: u8) -> Paragraph<'static> {
+	let rows = (0..4)
+		.map(|row| {
+			let keys = (0..4)
+				.map(|col| {
+					let key = row * 4 + col;
+					let text = format!(" {:X} ", key);
+					if key != value & 0x0F {
+						Span::raw(text)
+					} else if value & KEY_READY != 0 {
+						Span::styled(text, Style::default().add_modifier(Modifier::REVERSED))
+					} else {
+						Span::styled(text, Style::default().fg(Color::Yellow))
+					}
+				})
+				.collect::<Vec<_>>();
+			Spans::from(keys)
+		})
+		.collect::<Vec<_>>();
+	Paragraph::new(rows).block(
+		Block::default()
+			.borders(Borders::ALL)
+			.border_type(BorderType::Rounded)
+			.title("Keypad"),
+	)
 }
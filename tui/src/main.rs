@@ -6,18 +6,26 @@ use crossterm::{
 	terminal::{EnterAlternateScreen, LeaveAlternateScreen},
 };
 use error::RunTimeError;
-use flisp_lib::processor::Flisp;
+use flisp_lib::{
+	assembler::SymbolTable,
+	disassembler,
+	processor::Flisp,
+	watchpoint::{WatchKind, Watchpoint},
+	Board, Breakpoints, Bus, History, IllegalOpcodePolicy, IoDevice, LoopDetector, StopReason,
+	Trace, Watchpoints,
+};
 use tui::{
 	backend::CrosstermBackend,
-	layout::{Constraint, Direction, Layout},
-	text::Span,
-	widgets::{Block, BorderType, Borders, List, ListItem, Paragraph, Row, Table},
+	layout::{Constraint, Direction, Layout, Rect},
+	text::{Span, Spans},
+	widgets::{Block, BorderType, Borders, Clear, List, ListItem, Paragraph, Row, Table},
 	Terminal,
 };
 
 mod error;
 mod io_device;
-use io_device::IoDevice;
+
+use io_device::{DeviceWidget, KEYPAD_HEIGHT, KEYPAD_WIDTH};
 
 const MEM_SLICE: [u8; 256] = [
 	0x02, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -38,13 +46,13 @@ const MEM_SLICE: [u8; 256] = [
 	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x9B,
 ];
 
-fn write_mem(mem: &[u8; 256], out: &mut String) -> Result<()> {
+fn write_mem<B: Bus>(mem: &B, out: &mut String) -> Result<()> {
 	out.clear();
 	out.reserve(256 * 3);
 
-	for line in mem.chunks(16) {
-		for val in line.iter() {
-			write!(out, "{:02X} ", val)?;
+	for line in 0..16 {
+		for col in 0..16 {
+			write!(out, "{:02X} ", mem.peek(line * 16 + col))?;
 		}
 		out.push('\n');
 	}
@@ -52,19 +60,218 @@ fn write_mem(mem: &[u8; 256], out: &mut String) -> Result<()> {
 	Ok(())
 }
 
-fn handle_command(
-	cmd: &mut String,
-	flisp: &mut Flisp,
-	fb: &mut IoDevice,
-	fc: &mut IoDevice,
-	speed: &mut u64,
-) -> Result<()> {
-	cmd.make_ascii_lowercase();
-	let words = cmd.split_whitespace().collect::<Vec<_>>();
-	if words.is_empty() {
-		return Ok(());
+/// Rows taken by each device panel, room for segment art inside the border
+const DEVICE_HEIGHT: u16 = 5;
+
+/// Steps that can be undone
+const HISTORY_LENGTH: usize = 1024;
+/// Steps kept to notice a program going around in a loop
+const LOOP_WINDOW: usize = 256;
+
+/// What the number and letter keys are for
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Input {
+	/// The usual controls
+	Normal,
+	/// 0-7 flip the DIL switches
+	Switches,
+	/// 0-F press keys on the keypad
+	Keypad,
+}
+
+impl Input {
+	/// What each key does, shown in the controls panel
+	fn key_bindings(&self) -> &'static [(&'static str, &'static str)] {
+		match self {
+			Input::Normal => &[
+				("Step", "[H]"),
+				("Run", "[J]"),
+				("Faster", "[K]"),
+				("Slower", "[L]"),
+				("Back", "[B]"),
+				("Reverse continue", "[V]"),
+				("Switches", "[I]"),
+				("Keypad", "[P]"),
+				("Interrupt", "[N]"),
+				("Command", "[:]"),
+			],
+			Input::Switches => &[
+				("Toggle switch", "[0]-[7]"),
+				("Step", "[H]"),
+				("Run", "[J]"),
+				("Done", "[Esc]"),
+			],
+			Input::Keypad => &[
+				("Press key", "[0]-[F]"),
+				("Step", "[H]"),
+				("Run", "[J]"),
+				("Done", "[Esc]"),
+			],
+		}
+	}
+
+	/// The key bindings laid out in as many lines as it takes to fit in `width` columns
+	fn controls(&self, width: usize) -> Vec<String> {
+		let mut lines = vec![String::new()];
+		for (action, keys) in self.key_bindings() {
+			let binding = format!("{}: {}", action, keys);
+			let line = lines.last_mut().unwrap();
+			if line.is_empty() {
+				*line = binding;
+			} else if line.len() + 4 + binding.len() <= width {
+				line.push_str("    ");
+				line.push_str(&binding);
+			} else {
+				lines.push(binding);
+			}
+		}
+		lines
 	}
-	match words[0] {
+}
+
+/// What commands and keys act on
+struct Session {
+	flisp: Flisp<Board>,
+	symbols: Option<SymbolTable>,
+	breakpoints: Breakpoints,
+	watchpoints: Watchpoints,
+	history: History<Board>,
+	detector: LoopDetector<Board>,
+	/// Only recorded while tracing is on
+	trace: Option<Trace>,
+	steps_per_second: u64,
+	pause: bool,
+	input: Input,
+	log: String,
+}
+
+impl Session {
+	/// Executes one instruction and pauses if that triggers a watchpoint, lands on a
+	/// breakpoint or the program has halted or is stuck in a loop. Returns whether it paused
+	fn step(&mut self) -> Result<bool> {
+		let pc = self.flisp.PC;
+		let interrupted = self.flisp.irq_pending();
+		self.history.record(&self.flisp);
+		match &mut self.trace {
+			Some(trace) => trace.step(&mut self.flisp),
+			None => self.flisp.step(),
+		};
+		if interrupted {
+			writeln!(
+				self.log,
+				"   Interrupt at ${:02X}, jumped through $FD to ${:02X}",
+				pc, self.flisp.PC
+			)?;
+		}
+		let hits = self.watchpoints.check(pc, &self.flisp.accesses);
+		let mut stop = !hits.is_empty();
+		for hit in hits {
+			let inst = flisp_lib::decode(&self.flisp.mem, pc)
+				.map(|inst| inst.to_string())
+				.unwrap_or_default();
+			writeln!(self.log, "   Watchpoint {}: {}", hit, inst.trim_end())?;
+		}
+		if let Some(illegal) = self.flisp.illegal_opcode {
+			match self.flisp.on_illegal_opcode {
+				IllegalOpcodePolicy::Halt => {
+					stop = true;
+					writeln!(self.log, "   {}, halted", illegal)?;
+				}
+				_ => writeln!(
+					self.log,
+					"   {}, trapped through $FD to ${:02X}",
+					illegal, self.flisp.PC
+				)?,
+			}
+		}
+		if self.breakpoints.hit(&self.flisp) {
+			stop = true;
+			writeln!(self.log, "   Breakpoint at ${:02X}", self.flisp.PC)?;
+		}
+		let board = &self.flisp.mem;
+		let reason = if board.fb.is_input() || board.fc.is_input() {
+			// What the program reads can still change, so going around isn't being stuck
+			Some(StopReason::Halted(self.flisp.PC)).filter(|_| self.flisp.halted())
+		} else {
+			self.detector.check(&self.flisp)
+		};
+		if let Some(reason) = reason {
+			stop = true;
+			// Start over, so it takes another time around to report it again
+			self.detector.clear();
+			writeln!(self.log, "   {}", reason)?;
+		}
+		if stop {
+			self.pause = true;
+		}
+		Ok(stop)
+	}
+
+	/// Switches to `input` if a device to take it is attached
+	fn start_input(&mut self, input: Input) -> Result<()> {
+		let wanted = |device: &IoDevice| match input {
+			Input::Normal => true,
+			Input::Switches => matches!(device, IoDevice::DILSwitch(_)),
+			Input::Keypad => matches!(device, IoDevice::KeyPad(_)),
+		};
+		if !wanted(&self.flisp.mem.fb) && !wanted(&self.flisp.mem.fc) {
+			return Err(RunTimeError::NoInputDevice.into());
+		}
+		self.input = input;
+		Ok(())
+	}
+
+	/// Passes a key to the input devices. Returns false if it isn't one of theirs
+	fn enter(&mut self, key: char) -> bool {
+		let board = &mut self.flisp.mem;
+		match self.input {
+			Input::Normal => return false,
+			Input::Switches => match key.to_digit(8) {
+				Some(bit) => {
+					board.fb.toggle_switch(bit as u8);
+					board.fc.toggle_switch(bit as u8);
+				}
+				None => return false,
+			},
+			Input::Keypad => match key.to_digit(16) {
+				Some(digit) => {
+					board.fb.press_key(digit as u8);
+					board.fc.press_key(digit as u8);
+				}
+				None => return false,
+			},
+		}
+		true
+	}
+
+	/// Looks up an address given as a number or a label from the loaded program
+	fn address(&self, word: &str) -> Result<u8> {
+		let num = if let Some(hex) = word.strip_prefix('$') {
+			u8::from_str_radix(hex, 16).ok()
+		} else {
+			word.parse().ok()
+		};
+		let label = || {
+			self.symbols.as_ref().and_then(|symbols| {
+				symbols
+					.iter()
+					.find(|(name, _)| name.eq_ignore_ascii_case(word))
+					.map(|(_, symbol)| symbol.value)
+			})
+		};
+		Ok(num.or_else(label).ok_or(RunTimeError::MalformedArgument)?)
+	}
+}
+
+/// Runs one command line. Only the command name is case insensitive, the arguments are passed
+/// on as typed so file paths keep their case
+fn handle_command(cmd: &str, session: &mut Session) -> Result<()> {
+	let words = cmd.split_whitespace().collect::<Vec<_>>();
+	let command = match words.first() {
+		Some(word) => word.to_ascii_lowercase(),
+		None => return Ok(()),
+	};
+	match command.as_str() {
 		"step" => {
 			let steps = words
 				.get(1)
@@ -74,47 +281,202 @@ fn handle_command(
 				})
 				.unwrap_or(Ok(1))?;
 			for _ in 0..steps {
-				flisp.step();
+				if session.step()? {
+					break;
+				}
+			}
+		}
+		"break" => {
+			let adr = match words.get(1) {
+				Some(word) => session.address(word)?,
+				None => {
+					for (adr, condition) in session.breakpoints.iter() {
+						match condition {
+							Some(condition) =>
+								writeln!(session.log, "   ${:02X} if {}", adr, condition)?,
+							None => writeln!(session.log, "   ${:02X}", adr)?,
+						}
+					}
+					return Ok(());
+				}
+			};
+			let condition = match words.get(2) {
+				Some(&"if") => Some(
+					words[3..]
+						.join(" ")
+						.parse()
+						.map_err(|_| RunTimeError::MalformedArgument)?,
+				),
+				Some(_) => return Err(RunTimeError::MalformedArgument.into()),
+				None => None,
+			};
+			session.breakpoints.set(adr, condition);
+		}
+		"delete" => match words.get(1) {
+			Some(word) => {
+				let adr = session.address(word)?;
+				if !session.breakpoints.remove(adr) {
+					return Err(RunTimeError::NoSuchBreakpoint.into());
+				}
+			}
+			None => session.breakpoints.clear(),
+		},
+		"watch" => {
+			let range = match words.get(1) {
+				Some(range) => range,
+				None => {
+					for (idx, watchpoint) in session.watchpoints.iter().enumerate() {
+						writeln!(session.log, "   {}: {}", idx, watchpoint)?;
+					}
+					return Ok(());
+				}
+			};
+			let (start, end) = match range.split_once('-') {
+				Some((start, end)) => (session.address(start)?, session.address(end)?),
+				None => {
+					let adr = session.address(range)?;
+					(adr, adr)
+				}
+			};
+			let kind = match words.get(2) {
+				Some(&"read") => WatchKind::Read,
+				Some(&"write") | None => WatchKind::Write,
+				Some(&"change") => WatchKind::Change,
+				Some(_) => return Err(RunTimeError::MalformedArgument.into()),
+			};
+			session
+				.watchpoints
+				.add(Watchpoint::new(start.min(end), start.max(end), kind));
+		}
+		"unwatch" => match words.get(1) {
+			Some(idx) => {
+				let idx = idx.parse().map_err(|_| RunTimeError::MalformedArgument)?;
+				session
+					.watchpoints
+					.remove(idx)
+					.ok_or(RunTimeError::NoSuchWatchpoint)?;
 			}
+			None => session.watchpoints.clear(),
+		},
+		"back" => {
+			let found = match words.get(1) {
+				Some(word) => {
+					let adr = session.address(word)?;
+					session.history.run_back_to(&mut session.flisp, adr)
+				}
+				None => session.history.step_back(&mut session.flisp),
+			};
+			if !found {
+				return Err(RunTimeError::HistoryExhausted.into());
+			}
+			session.detector.clear();
+		}
+		"trace" => match words.get(1) {
+			Some(&"on") => {
+				session.trace.get_or_insert_with(Trace::new);
+			}
+			Some(&"off") => session.trace = None,
+			Some(&"save") => {
+				let file_path = words.get(2).ok_or(RunTimeError::MissingArgument)?;
+				let trace = session.trace.as_ref().ok_or(RunTimeError::NotTracing)?;
+				let mut out = String::new();
+				if file_path.ends_with(".csv") {
+					trace.write_csv(&mut out)?;
+				} else if file_path.ends_with(".jsonl") || file_path.ends_with(".json") {
+					trace.write_json_lines(&mut out)?;
+				} else {
+					trace.write_text(&mut out)?;
+				}
+				std::fs::write(file_path, out).map_err(|_| RunTimeError::BadFilePath)?;
+			}
+			_ => return Err(RunTimeError::MalformedArgument.into()),
+		},
+		"illegal" =>
+			session.flisp.on_illegal_opcode = match words.get(1) {
+				Some(&"trap") => IllegalOpcodePolicy::Trap,
+				Some(&"halt") => IllegalOpcodePolicy::Halt,
+				_ => return Err(RunTimeError::MalformedArgument.into()),
+			},
+		"continue" => {
+			session.pause = false;
 		}
 		"load" => {
 			let file_path = words.get(1).ok_or(RunTimeError::MalformedArgument)?;
 			let file = std::fs::read_to_string(file_path).map_err(|_| RunTimeError::BadFilePath)?;
-			let new_flisp = Flisp::from_str(&file).map_err(|_| RunTimeError::BadFile)?;
-			flisp.mem = new_flisp.mem;
+			let (new_flisp, new_symbols) = if file_path.ends_with(".sflisp")
+				|| file_path.ends_with(".asm")
+			{
+				let (new_flisp, new_symbols) = flisp_lib::assembler::assemble_with_symbols(&file)
+					.map_err(|e| e.in_file(file_path))?;
+				(new_flisp, Some(new_symbols))
+			} else if file_path.ends_with(".s19") || file_path.ends_with(".srec") {
+				(Flisp::from_srecords(&file)?, None)
+			} else if file_path.ends_with(".hex") || file_path.ends_with(".ihx") {
+				(Flisp::from_intel_hex(&file)?, None)
+			} else {
+				(
+					Flisp::from_str(&file).map_err(|_| RunTimeError::BadFile)?,
+					None,
+				)
+			};
+			session.symbols = new_symbols;
+			session.history.clear();
+			session.detector.clear();
+			session.flisp.mem.ram = new_flisp.mem;
+		}
+		"export" => {
+			let file_path = words.get(1).ok_or(RunTimeError::MissingArgument)?;
+			let mut out = String::new();
+			if file_path.ends_with(".s19") || file_path.ends_with(".srec") {
+				session.flisp.write_srecords(&mut out)?;
+			} else if file_path.ends_with(".hex") || file_path.ends_with(".ihx") {
+				session.flisp.write_intel_hex(&mut out)?;
+			} else {
+				return Err(RunTimeError::BadFilePath.into());
+			}
+			std::fs::write(file_path, out).map_err(|_| RunTimeError::BadFilePath)?;
+		}
+		"save" => {
+			let file_path = words.get(1).ok_or(RunTimeError::MissingArgument)?;
+			let mut out = String::new();
+			session.flisp.write_snapshot(&mut out)?;
+			std::fs::write(file_path, out).map_err(|_| RunTimeError::BadFilePath)?;
+		}
+		"restore" => {
+			let file_path = words.get(1).ok_or(RunTimeError::MissingArgument)?;
+			let file = std::fs::read_to_string(file_path).map_err(|_| RunTimeError::BadFilePath)?;
+			let restored = Flisp::from_snapshot(&file)?;
+			session.flisp = Flisp {
+				on_illegal_opcode: session.flisp.on_illegal_opcode,
+				..restored
+			};
+			session.symbols = None;
+			session.history.clear();
+			session.detector.clear();
 		}
 		"reset" => {
-			flisp.A = 0;
-			flisp.X = 0;
-			flisp.Y = 0;
-			flisp.CC = 0;
-			flisp.SP = 0;
-			flisp.PC = 0xFF;
+			session.flisp.reset();
+			session.detector.clear();
 		}
 		"speed" => {
 			let num_str = words.get(1).ok_or(RunTimeError::MissingArgument)?;
 			let num: u64 = num_str
 				.parse()
 				.map_err(|_| RunTimeError::MalformedArgument)?;
-			*speed = num;
+			session.steps_per_second = num;
 		}
 		"io" => {
 			let dev = match words.get(1) {
-				Some(&"fb") => fb,
-				Some(&"fc") => fc,
+				Some(&"fb") => &mut session.flisp.mem.fb,
+				Some(&"fc") => &mut session.flisp.mem.fc,
 				_ => return Err(RunTimeError::InvalidIOPort.into()),
 			};
 			*dev = match words.get(2) {
 				Some(&"clear") | None => IoDevice::Nothing,
-				Some(&"bargraph") => IoDevice::Bargraph(0),
-				Some(&"hexdisplay") => IoDevice::HexDisplay(0),
-				Some(&"sevenseg") => IoDevice::SevenSeg(0),
-				Some(&"steppermotor") => IoDevice::StepperMotor(0),
-				Some(&"dilswitch") => IoDevice::DILSwitch(0),
-				Some(&"keypad") => IoDevice::KeyPad(0),
-				Some(&"irqflipflop") => IoDevice::IRQFlipFlop(0),
-				_ => return Err(RunTimeError::InvalidDeviceType.into()),
-			}
+				Some(name) => IoDevice::from_name(name).ok_or(RunTimeError::InvalidDeviceType)?,
+			};
+			// The device taking keys may be gone
+			session.input = Input::Normal;
 		}
 		_ => return Err(RunTimeError::InvalidCommand.into()),
 	}
@@ -123,16 +485,19 @@ fn handle_command(
 
 fn main() -> Result<()> {
 	execute!(io::stdout(), EnterAlternateScreen)?;
-	let mut flisp = Flisp {
-		A: 0,
-		X: 0,
-		Y: 0,
-		SP: 0,
-		CC: 0,
-		PC: 0xFF,
-		mem: MEM_SLICE,
+	let mut session = Session {
+		flisp: Flisp::new(Board::new(MEM_SLICE)),
+		symbols: None,
+		breakpoints: Breakpoints::new(),
+		watchpoints: Watchpoints::new(),
+		history: History::new(HISTORY_LENGTH),
+		detector: LoopDetector::new(LOOP_WINDOW),
+		trace: None,
+		steps_per_second: 1,
+		pause: true,
+		input: Input::Normal,
+		log: String::new(),
 	};
-	flisp.PC = flisp.mem[flisp.PC as usize];
 
 	let backend_stdout = io::stdout();
 	let backend = CrosstermBackend::new(backend_stdout);
@@ -141,11 +506,6 @@ fn main() -> Result<()> {
 	let stdin = io::stdin();
 	terminal::enable_raw_mode()?;
 
-	let mut steps_per_second = 1;
-	let mut pause = true;
-	let mut fb = IoDevice::Nothing;
-	let mut fc = IoDevice::Nothing;
-
 	let mut memory_text_buffer = String::new();
 	let mut register_a_buffer = String::new();
 	let mut register_x_buffer = String::new();
@@ -154,7 +514,6 @@ fn main() -> Result<()> {
 	let mut register_sp_buffer = String::new();
 	let mut register_cc_buffer = String::new();
 	let mut command_buffer = String::new();
-	let mut log = String::new();
 	let mut dis_asm_buffer = String::new();
 
 	'drawing_loop: loop {
@@ -167,38 +526,62 @@ fn main() -> Result<()> {
 		dis_asm_buffer.clear();
 		command_buffer.clear();
 
-		write_mem(&flisp.mem, &mut memory_text_buffer)?;
-		write!(&mut register_a_buffer, "0x{:02X}", flisp.A)?;
-		write!(&mut register_x_buffer, "0x{:02X}", flisp.X)?;
-		write!(&mut register_y_buffer, "0x{:02X}", flisp.Y)?;
-		write!(&mut register_pc_buffer, "0x{:02X}", flisp.PC)?;
-		write!(&mut register_sp_buffer, "0x{:02X}", flisp.SP)?;
-		write!(&mut register_cc_buffer, "0b {:05b}", flisp.CC)?;
-
-		let mut idx = flisp.PC;
-		loop {
-			let next = flisp.print_disassembly(&mut dis_asm_buffer, idx)?;
-			dis_asm_buffer.push('\n');
-			if next < idx {
-				break;
-			}
-			idx = next;
+		write_mem(&session.flisp.mem, &mut memory_text_buffer)?;
+		write!(&mut register_a_buffer, "0x{:02X}", session.flisp.A)?;
+		write!(&mut register_x_buffer, "0x{:02X}", session.flisp.X)?;
+		write!(&mut register_y_buffer, "0x{:02X}", session.flisp.Y)?;
+		write!(&mut register_pc_buffer, "0x{:02X}", session.flisp.PC)?;
+		write!(&mut register_sp_buffer, "0x{:02X}", session.flisp.SP)?;
+		write!(&mut register_cc_buffer, "0b {:05b}", session.flisp.CC)?;
+
+		let dis_asm = disassembler::disassemble_flow(&session.flisp, session.symbols.as_ref())?;
+		for line in dis_asm
+			.iter()
+			.skip_while(|line| line.adr < session.flisp.PC)
+		{
+			let marker = if session.breakpoints.contains(line.adr) {
+				'*'
+			} else {
+				' '
+			};
+			writeln!(&mut dis_asm_buffer, "{}{:02X} {}", marker, line.adr, line)?;
 		}
 		let items = dis_asm_buffer
 			.lines()
 			.map(ListItem::new)
 			.collect::<Vec<_>>();
 
-		let log_lines = log.lines().map(ListItem::new).collect::<Vec<_>>();
+		let log_lines = session.log.lines().map(ListItem::new).collect::<Vec<_>>();
+
+		let devices = [
+			("$FB", &session.flisp.mem.fb),
+			("$FC", &session.flisp.mem.fc),
+		]
+		.iter()
+		.filter(|(_, device)| **device != IoDevice::Nothing)
+		.map(|(port, device)| device.to_widget(port))
+		.collect::<Vec<_>>();
+		let keypad = [&session.flisp.mem.fb, &session.flisp.mem.fc]
+			.iter()
+			.find_map(|device| match device {
+				IoDevice::KeyPad(value) => Some(*value),
+				_ => None,
+			})
+			.filter(|_| session.input == Input::Keypad);
 
 		terminal.draw(|f| {
+			// Inside the margin and the borders
+			let controls = session
+				.input
+				.controls(f.size().width.saturating_sub(4) as usize);
+			let controls_height = controls.len() as u16 + 2;
 			let control_split = Layout::default()
 				.direction(Direction::Vertical)
 				.margin(1)
 				.constraints([
 					Constraint::Min(18),
-					Constraint::Min(f.size().height.saturating_sub(23)),
-					Constraint::Min(3),
+					Constraint::Min(f.size().height.saturating_sub(20 + controls_height)),
+					Constraint::Min(controls_height),
 				])
 				.split(f.size());
 			let ui_split = Layout::default()
@@ -206,8 +589,8 @@ fn main() -> Result<()> {
 				.constraints([
 					Constraint::Min(3 * 16 + 1),
 					Constraint::Min(10),
-					Constraint::Min(20),
-					Constraint::Min(f.size().width.saturating_sub(3 * 16 + 31)),
+					Constraint::Min(40),
+					Constraint::Min(f.size().width.saturating_sub(3 * 16 + 51)),
 				])
 				.split(control_split[0]);
 			let register_split = Layout::default()
@@ -265,10 +648,18 @@ fn main() -> Result<()> {
 			);
 			f.render_widget(dis_asm_list, ui_split[2]);
 
-			let controls_paragraph = Paragraph::new(Span::raw(
-				"Step: [H]    Run: [J]    Faster: [K]    Slower: [L]    Command: [:]",
-			))
-			.block(
+			let mut device_constraints = vec![Constraint::Length(DEVICE_HEIGHT); devices.len()];
+			device_constraints.push(Constraint::Min(0));
+			let device_split = Layout::default()
+				.direction(Direction::Vertical)
+				.constraints(device_constraints)
+				.split(ui_split[3]);
+			for (idx, device) in devices.into_iter().enumerate() {
+				f.render_widget(device, device_split[idx]);
+			}
+
+			let controls = controls.into_iter().map(Spans::from).collect::<Vec<_>>();
+			let controls_paragraph = Paragraph::new(controls).block(
 				Block::default()
 					.borders(Borders::ALL)
 					.border_type(BorderType::Rounded)
@@ -278,12 +669,24 @@ fn main() -> Result<()> {
 
 			let log_list = List::new(log_lines).block(Block::default());
 			f.render_widget(log_list, control_split[1]);
+
+			if let Some(value) = keypad {
+				let size = f.size();
+				let area = Rect::new(
+					size.width.saturating_sub(KEYPAD_WIDTH) / 2,
+					size.height.saturating_sub(KEYPAD_HEIGHT) / 2,
+					KEYPAD_WIDTH.min(size.width),
+					KEYPAD_HEIGHT.min(size.height),
+				);
+				f.render_widget(Clear, area);
+				f.render_widget(io_device::keypad_overlay(value), area);
+			}
 		})?;
 
-		let wait = if pause || steps_per_second == 0 {
+		let wait = if session.pause || session.steps_per_second == 0 {
 			u64::MAX
 		} else {
-			1000 / steps_per_second
+			1000 / session.steps_per_second
 		};
 		if event::poll(Duration::from_millis(wait))? {
 			if let event::Event::Key(key) = event::read()? {
@@ -294,18 +697,60 @@ fn main() -> Result<()> {
 						break 'drawing_loop;
 					}
 
+					event::KeyCode::Char(c) if session.enter(c) => {}
 					event::KeyCode::Char(c) => match c {
 						'h' => {
-							flisp.step();
+							session.step()?;
 						}
 						'j' => {
-							pause = !pause;
+							session.pause = !session.pause;
+						}
+						'b' => {
+							session.pause = true;
+							session.detector.clear();
+							if !session.history.step_back(&mut session.flisp) {
+								writeln!(session.log, "   {}", RunTimeError::HistoryExhausted)?;
+							}
+						}
+						'v' => {
+							session.pause = true;
+							session.detector.clear();
+							let Session {
+								flisp,
+								history,
+								breakpoints,
+								..
+							} = &mut session;
+							if !history.reverse_continue(flisp, breakpoints) {
+								writeln!(session.log, "   {}", RunTimeError::HistoryExhausted)?;
+							}
+						}
+						'i' | 'p' => {
+							let input = if c == 'i' {
+								Input::Switches
+							} else {
+								Input::Keypad
+							};
+							if let Err(e) = session.start_input(input) {
+								writeln!(session.log, "   {}", e)?;
+							}
+						}
+						'n' => {
+							let board = &mut session.flisp.mem;
+							if matches!(board.fb, IoDevice::IRQFlipFlop(_))
+								|| matches!(board.fc, IoDevice::IRQFlipFlop(_))
+							{
+								board.fb.trigger();
+								board.fc.trigger();
+							} else {
+								writeln!(session.log, "   {}", RunTimeError::NoInputDevice)?;
+							}
 						}
 						'k' => {
-							steps_per_second = steps_per_second.saturating_add(1);
+							session.steps_per_second = session.steps_per_second.saturating_add(1);
 						}
 						'l' => {
-							steps_per_second = steps_per_second.saturating_sub(1);
+							session.steps_per_second = session.steps_per_second.saturating_sub(1);
 						}
 						':' => {
 							let (_, height) = crossterm::terminal::size()?;
@@ -324,23 +769,24 @@ fn main() -> Result<()> {
 								terminal::ScrollDown(1),
 								terminal::Clear(terminal::ClearType::FromCursorDown)
 							)?;
-							let res = handle_command(
-								&mut command_buffer,
-								&mut flisp,
-								&mut fb,
-								&mut fc,
-								&mut steps_per_second,
-							);
-							log.push_str(" >");
-							log.push_str(&command_buffer);
-							log.push('\n');
+							session.log.push_str(" >");
+							session.log.push_str(&command_buffer);
+							session.log.push('\n');
+							let res = handle_command(&command_buffer, &mut session);
 							if let Err(e) = res {
-								writeln!(log, "   {}", e)?;
+								writeln!(
+									session.log,
+									"   {}",
+									e.to_string().replace('\n', "\n   ")
+								)?;
 							}
 						}
 						_ => {}
 					},
 
+					event::KeyCode::Esc if session.input != Input::Normal => {
+						session.input = Input::Normal;
+					}
 					event::KeyCode::Esc => {
 						break 'drawing_loop;
 					}
@@ -348,7 +794,7 @@ fn main() -> Result<()> {
 				}
 			}
 		} else {
-			flisp.step();
+			session.step()?;
 		}
 	}
 